@@ -0,0 +1,160 @@
+//! Account addresses for I Protocol
+//!
+//! An address is derived from an Ed25519 public key:
+//!
+//! ```text
+//! payload  = version (1 byte) || Blake3(public_key)[..20]
+//! checksum = Blake3(payload)[..4]
+//! address  = Base58(payload || checksum)
+//! ```
+
+use crate::primitives::{HexError, PublicKey};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Version byte of mainnet addresses
+pub const ADDRESS_VERSION: u8 = 0x00;
+/// Length of the public key hash carried by an address
+pub const ADDRESS_HASH_LEN: usize = 20;
+/// Length of the raw address (version + hash)
+pub const ADDRESS_LEN: usize = 1 + ADDRESS_HASH_LEN;
+/// Length of the address checksum
+const CHECKSUM_LEN: usize = 4;
+/// Length of the decoded address (version + hash + checksum)
+const ENCODED_LEN: usize = ADDRESS_LEN + CHECKSUM_LEN;
+
+/// Errors produced when parsing or deriving an address
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AddressError {
+    #[error("address is not valid base58: {0}")]
+    InvalidEncoding(String),
+    #[error("address has invalid length {0} (expected {ENCODED_LEN} bytes)")]
+    InvalidLength(usize),
+    #[error("unsupported address version {0:#04x}")]
+    UnsupportedVersion(u8),
+    #[error("address checksum mismatch")]
+    ChecksumMismatch,
+    #[error("invalid Ed25519 public key: {0}")]
+    InvalidPublicKey(String),
+}
+
+/// Canonical account address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address {
+    version: u8,
+    hash: [u8; ADDRESS_HASH_LEN],
+}
+
+impl Address {
+    /// Derive the address of an Ed25519 public key
+    pub fn from_public_key(public_key: &VerifyingKey) -> Self {
+        let digest = blake3::hash(public_key.as_bytes());
+        let mut hash = [0u8; ADDRESS_HASH_LEN];
+        hash.copy_from_slice(&digest.as_bytes()[..ADDRESS_HASH_LEN]);
+
+        Address {
+            version: ADDRESS_VERSION,
+            hash,
+        }
+    }
+
+    /// Derive the address of a hex-encoded Ed25519 public key
+    pub fn from_public_key_hex(public_key: &str) -> Result<Self, AddressError> {
+        Ok(Self::from_public_key(&parse_public_key(public_key)?))
+    }
+
+    /// Version byte of this address
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Public key hash carried by this address
+    pub fn hash(&self) -> &[u8; ADDRESS_HASH_LEN] {
+        &self.hash
+    }
+
+    /// Raw address: the version byte followed by the public key hash
+    pub fn to_bytes(&self) -> [u8; ADDRESS_LEN] {
+        let mut bytes = [0u8; ADDRESS_LEN];
+        bytes[0] = self.version;
+        bytes[1..].copy_from_slice(&self.hash);
+        bytes
+    }
+
+    /// Address from its raw bytes, checking the version
+    pub fn from_bytes(bytes: [u8; ADDRESS_LEN]) -> Result<Self, AddressError> {
+        if bytes[0] != ADDRESS_VERSION {
+            return Err(AddressError::UnsupportedVersion(bytes[0]));
+        }
+        let mut hash = [0u8; ADDRESS_HASH_LEN];
+        hash.copy_from_slice(&bytes[1..]);
+        Ok(Address {
+            version: bytes[0],
+            hash,
+        })
+    }
+}
+
+/// Decode a hex-encoded Ed25519 public key
+pub fn parse_public_key(public_key: &str) -> Result<VerifyingKey, AddressError> {
+    let public_key: PublicKey = public_key
+        .parse()
+        .map_err(|e: HexError| AddressError::InvalidPublicKey(e.to_string()))?;
+    public_key
+        .verifying_key()
+        .map_err(|e| AddressError::InvalidPublicKey(e.to_string()))
+}
+
+/// First four bytes of the Blake3 hash of the payload
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    checksum.copy_from_slice(&blake3::hash(payload).as_bytes()[..CHECKSUM_LEN]);
+    checksum
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let payload = self.to_bytes();
+        let mut bytes = Vec::with_capacity(ENCODED_LEN);
+        bytes.extend_from_slice(&payload);
+        bytes.extend_from_slice(&checksum(&payload));
+        write!(f, "{}", bs58::encode(bytes).into_string())
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = bs58::decode(s)
+            .into_vec()
+            .map_err(|e| AddressError::InvalidEncoding(e.to_string()))?;
+        if bytes.len() != ENCODED_LEN {
+            return Err(AddressError::InvalidLength(bytes.len()));
+        }
+
+        let (payload, check) = bytes.split_at(ADDRESS_LEN);
+        if checksum(payload) != check {
+            return Err(AddressError::ChecksumMismatch);
+        }
+        let mut raw = [0u8; ADDRESS_LEN];
+        raw.copy_from_slice(payload);
+        Self::from_bytes(raw)
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
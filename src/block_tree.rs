@@ -0,0 +1,135 @@
+//! Index of every known block, including side branches
+//!
+//! Blocks are keyed by their header hash and linked to their parent through
+//! `parent_hash`. Each entry records the cumulative work of the branch ending at
+//! that block, which is what fork choice compares: the active chain is always the
+//! branch with the most work, not the longest one.
+
+use crate::primitives::Hash32;
+use crate::uint::U256;
+use crate::Block;
+use std::collections::{HashMap, HashSet};
+
+/// A block stored in the tree
+#[derive(Debug, Clone)]
+pub struct BlockNode {
+    /// The block itself
+    pub block: Block,
+    /// Sum of the work of this block and all of its ancestors
+    pub chain_work: U256,
+}
+
+impl BlockNode {
+    /// Height of the block
+    pub fn height(&self) -> u64 {
+        self.block.header.block_height
+    }
+
+    /// Hash of the parent block
+    pub fn parent_hash(&self) -> &Hash32 {
+        &self.block.header.parent_hash
+    }
+}
+
+/// Blocks indexed by hash
+#[derive(Debug, Clone, Default)]
+pub struct BlockTree {
+    nodes: HashMap<Hash32, BlockNode>,
+}
+
+impl BlockTree {
+    /// Create an empty tree
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a block under its hash and return its cumulative work
+    ///
+    /// The parent must already be in the tree unless the block is a root
+    /// (the genesis block), whose cumulative work is its own work.
+    pub fn insert(&mut self, hash: Hash32, block: Block) -> U256 {
+        let parent_work = self
+            .nodes
+            .get(&block.header.parent_hash)
+            .map_or(U256::ZERO, |parent| parent.chain_work);
+        let chain_work = parent_work.saturating_add(&block.header.work());
+        self.nodes.insert(hash, BlockNode { block, chain_work });
+        chain_work
+    }
+
+    /// Look up a block by hash
+    pub fn get(&self, hash: &Hash32) -> Option<&BlockNode> {
+        self.nodes.get(hash)
+    }
+
+    /// Whether a block with this hash is known
+    pub fn contains(&self, hash: &Hash32) -> bool {
+        self.nodes.contains_key(hash)
+    }
+
+    /// Number of known blocks
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether the tree holds no blocks
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Hash and cumulative work of the block with the most cumulative work
+    pub fn most_work(&self) -> Option<(Hash32, U256)> {
+        self.nodes
+            .iter()
+            .max_by(|(_, a), (_, b)| a.chain_work.cmp(&b.chain_work))
+            .map(|(hash, node)| (*hash, node.chain_work))
+    }
+
+    /// Hash of the ancestor at `height` of the block `hash` (the block itself at its own height)
+    pub fn ancestor(&self, hash: &Hash32, height: u64) -> Option<Hash32> {
+        let (mut hash, mut node) = self.nodes.get_key_value(hash)?;
+        while node.height() > height {
+            (hash, node) = self.nodes.get_key_value(node.parent_hash())?;
+        }
+        (node.height() == height).then_some(*hash)
+    }
+
+    /// Hashes from `tip` back to (excluding) the first block for which
+    /// `is_stop` returns true, ordered from the lowest block up to `tip`
+    pub fn branch_until(&self, tip: &Hash32, mut is_stop: impl FnMut(&Hash32, &BlockNode) -> bool) -> Vec<Hash32> {
+        let mut branch = Vec::new();
+        let mut current = tip;
+        while let Some((hash, node)) = self.nodes.get_key_value(current) {
+            if is_stop(hash, node) {
+                break;
+            }
+            branch.push(*hash);
+            current = node.parent_hash();
+        }
+        branch.reverse();
+        branch
+    }
+
+    /// Remove a block and every block built on top of it, returning the number removed
+    pub fn remove_with_descendants(&mut self, hash: &Hash32) -> usize {
+        if self.nodes.remove(hash).is_none() {
+            return 0;
+        }
+        let mut removed: HashSet<Hash32> = HashSet::from([*hash]);
+        loop {
+            let children: Vec<Hash32> = self
+                .nodes
+                .iter()
+                .filter(|(_, node)| removed.contains(node.parent_hash()))
+                .map(|(hash, _)| *hash)
+                .collect();
+            if children.is_empty() {
+                return removed.len();
+            }
+            for child in children {
+                self.nodes.remove(&child);
+                removed.insert(child);
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Consensus parameters and block-level rules shared by mining and validation
+
+use crate::difficulty::{self, DEFAULT_RETARGET_INTERVAL, DEFAULT_TARGET_BLOCK_TIME_SECS, DIFFICULTY_1_BITS};
+use crate::error::{BlockError, TransactionError};
+use crate::monetary::MonetaryPolicy;
+use crate::{Block, Transaction, COIN};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Default number of previous blocks whose median timestamp a new block must exceed
+pub const DEFAULT_MEDIAN_TIME_SPAN: u64 = 11;
+/// Default limit on how far ahead of the local clock a block timestamp may be, in seconds
+pub const DEFAULT_MAX_FUTURE_DRIFT_SECS: u64 = 2 * 60 * 60;
+/// Default limit on the encoded size of a block, in bytes
+pub const DEFAULT_MAX_BLOCK_SIZE: u64 = 1_000_000;
+/// Default limit on the number of transactions in a block, including the coinbase
+pub const DEFAULT_MAX_BLOCK_TRANSACTIONS: u32 = 5_000;
+/// Default minimum fee of any non-coinbase transaction
+pub const DEFAULT_MIN_TRANSACTION_FEE: u64 = 1_000;
+/// Default minimum fee per byte of a transaction's wire encoding
+pub const DEFAULT_MIN_FEE_PER_BYTE: u64 = 5;
+/// Smallest accepted block size limit: room for a header and a coinbase
+pub const MIN_BLOCK_SIZE_LIMIT: u64 = 1_000;
+/// Largest accepted block size limit, in bytes
+pub const MAX_BLOCK_SIZE_LIMIT: u64 = 64_000_000;
+/// Largest accepted limit on the number of transactions in a block
+pub const MAX_BLOCK_TRANSACTIONS_LIMIT: u32 = 1_000_000;
+/// Largest accepted minimum transaction fee (1 I)
+pub const MAX_MIN_TRANSACTION_FEE: u64 = COIN;
+/// Largest accepted minimum fee per byte
+pub const MAX_MIN_FEE_PER_BYTE: u64 = 100_000;
+
+/// Rules every node on a network must agree on
+///
+/// Fields missing when deserializing take their default values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsensusParams {
+    /// Compact Proof of Work target of the genesis block and of every block until
+    /// the first retarget
+    pub initial_bits: u32,
+    /// Compact encoding of the easiest target a retarget may reach
+    pub pow_limit_bits: u32,
+    /// Block interval the difficulty adjustment aims for, in seconds
+    pub target_block_time_secs: u64,
+    /// Number of blocks between difficulty adjustments (0 disables retargeting)
+    pub retarget_interval: u64,
+    /// Number of previous blocks whose median timestamp (median-time-past) a new
+    /// block's timestamp must exceed
+    pub median_time_span: u64,
+    /// How far ahead of the local clock a block timestamp may be, in seconds
+    pub max_future_drift_secs: u64,
+    /// Emission schedule of the subsidy paid to miners on top of the collected fees
+    pub monetary: MonetaryPolicy,
+    /// Maximum encoded size of a block, in bytes
+    pub max_block_size: u64,
+    /// Maximum number of transactions in a block, including the coinbase
+    pub max_block_transactions: u32,
+    /// Minimum fee of every non-coinbase transaction
+    pub min_transaction_fee: u64,
+    /// Minimum fee per byte of a non-coinbase transaction's wire encoding
+    pub min_fee_per_byte: u64,
+}
+
+impl ConsensusParams {
+    /// Default parameters with the given initial difficulty (expected hashes per block)
+    pub fn with_difficulty(difficulty: u64) -> Self {
+        ConsensusParams {
+            initial_bits: difficulty::bits_from_difficulty(difficulty),
+            ..Self::default()
+        }
+    }
+
+    /// Minimum fee a non-coinbase transaction of `size` encoded bytes must pay
+    pub fn min_fee(&self, size: usize) -> u64 {
+        fee_floor(self.min_transaction_fee, self.min_fee_per_byte, size)
+    }
+}
+
+impl Default for ConsensusParams {
+    fn default() -> Self {
+        ConsensusParams {
+            initial_bits: difficulty::bits_from_difficulty(1 << 16),
+            pow_limit_bits: DIFFICULTY_1_BITS,
+            target_block_time_secs: DEFAULT_TARGET_BLOCK_TIME_SECS,
+            retarget_interval: DEFAULT_RETARGET_INTERVAL,
+            median_time_span: DEFAULT_MEDIAN_TIME_SPAN,
+            max_future_drift_secs: DEFAULT_MAX_FUTURE_DRIFT_SECS,
+            monetary: MonetaryPolicy::default(),
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            max_block_transactions: DEFAULT_MAX_BLOCK_TRANSACTIONS,
+            min_transaction_fee: DEFAULT_MIN_TRANSACTION_FEE,
+            min_fee_per_byte: DEFAULT_MIN_FEE_PER_BYTE,
+        }
+    }
+}
+
+/// Median of a set of block timestamps (the upper median for an even count)
+pub fn median_time(mut timestamps: Vec<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    timestamps.sort_unstable();
+    timestamps.get(timestamps.len() / 2).copied()
+}
+
+/// Check a block timestamp against the median-time-past of its ancestors and the
+/// local clock
+///
+/// The timestamp must be strictly after `median_time_past` and at most
+/// `max_future_drift_secs` ahead of `now`.
+pub fn validate_timestamp(
+    params: &ConsensusParams,
+    timestamp: DateTime<Utc>,
+    median_time_past: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<(), BlockError> {
+    if timestamp <= median_time_past {
+        return Err(BlockError::TimestampNotAfterMedian {
+            timestamp,
+            median_time_past,
+        });
+    }
+    let drift = chrono::Duration::seconds(params.max_future_drift_secs.min(i64::MAX as u64) as i64);
+    let limit = now + drift;
+    if timestamp > limit {
+        return Err(BlockError::TimestampTooFarInFuture { timestamp, limit });
+    }
+    Ok(())
+}
+
+/// Check that a block stays within the transaction count and size limits
+pub fn validate_block_limits(params: &ConsensusParams, block: &Block) -> Result<(), BlockError> {
+    let limit = params.max_block_transactions;
+    if block.transactions.len() > limit as usize {
+        return Err(BlockError::TooManyTransactions { count: block.transactions.len(), limit });
+    }
+    let size = block.size();
+    if size as u64 > params.max_block_size {
+        return Err(BlockError::BlockTooLarge { size, limit: params.max_block_size });
+    }
+    Ok(())
+}
+
+/// Fee required of a transaction of `size` encoded bytes by a flat minimum and a
+/// minimum per byte: the higher of the two
+pub fn fee_floor(per_transaction: u64, per_byte: u64, size: usize) -> u64 {
+    per_transaction.max(per_byte.saturating_mul(size as u64))
+}
+
+/// Check that a non-coinbase transaction pays at least the consensus minimum fee
+pub fn validate_fee(params: &ConsensusParams, transaction: &Transaction) -> Result<(), TransactionError> {
+    let minimum = params.min_fee(transaction.size());
+    if transaction.fee < minimum {
+        return Err(TransactionError::FeeTooLow { fee: transaction.fee, minimum });
+    }
+    Ok(())
+}
+
+/// Sum of the fees paid by the non-coinbase transactions of a block
+pub fn total_fees(block: &Block) -> Result<u64, BlockError> {
+    block
+        .transactions
+        .iter()
+        .filter(|tx| !tx.is_coinbase())
+        .try_fold(0u64, |total, tx| total.checked_add(tx.fee))
+        .ok_or(BlockError::FeeOverflow)
+}
+
+/// Check that a non-genesis block starts with a well-formed coinbase paying
+/// exactly `subsidy` plus the fees of the block
+///
+/// `subsidy` is the scheduled subsidy for the block height, so a coinbase can
+/// never issue more than the monetary policy allows.
+pub fn validate_coinbase(block: &Block, subsidy: u64) -> Result<(), BlockError> {
+    let height = block.header.block_height;
+    let coinbase = match block.transactions.first() {
+        Some(tx) if tx.is_coinbase() => tx,
+        _ => return Err(BlockError::MissingCoinbase { height }),
+    };
+
+    if coinbase.nonce != height {
+        return Err(BlockError::InvalidCoinbaseNonce {
+            expected: height,
+            actual: coinbase.nonce,
+        });
+    }
+    if coinbase.fee != 0 {
+        return Err(BlockError::CoinbaseFee(coinbase.fee));
+    }
+    if coinbase.signature.is_some() {
+        return Err(BlockError::SignedCoinbase);
+    }
+    let expected_id = coinbase.calculate_hash();
+    if coinbase.txn_id != expected_id {
+        return Err(BlockError::InvalidCoinbase(TransactionError::TxnIdMismatch {
+            expected: expected_id,
+            actual: coinbase.txn_id,
+        }));
+    }
+    let expected = subsidy
+        .checked_add(total_fees(block)?)
+        .ok_or(BlockError::FeeOverflow)?;
+    if coinbase.amount != expected {
+        return Err(BlockError::InvalidCoinbaseAmount {
+            expected,
+            actual: coinbase.amount,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Proof of Work targets and difficulty retargeting
+//!
+//! A block is valid when its header hash, read as a 256-bit big-endian integer,
+//! is at or below the target encoded in the header's compact `bits` field. The
+//! compact form is the usual 32-bit floating point encoding: one exponent byte
+//! (length of the target in bytes) followed by a three-byte mantissa.
+//!
+//! Difficulty is the ratio between the easiest target ([`DIFFICULTY_1_BITS`])
+//! and a block's target, so difficulty `d` takes about `d` hashes to solve. It is
+//! re-evaluated every `retarget_interval` blocks by scaling the target with the
+//! ratio between the observed and the expected time to mine the last window.
+
+use crate::consensus::ConsensusParams;
+use crate::uint::U256;
+use crate::BlockHeader;
+use std::fmt;
+
+/// Default target time between blocks, in seconds
+pub const DEFAULT_TARGET_BLOCK_TIME_SECS: u64 = 60;
+/// Default number of blocks between difficulty adjustments
+pub const DEFAULT_RETARGET_INTERVAL: u64 = 10;
+/// Longest target time between blocks a network may configure, in seconds
+pub const MAX_TARGET_BLOCK_TIME_SECS: u64 = 24 * 60 * 60;
+/// Most blocks between difficulty adjustments a network may configure
+pub const MAX_RETARGET_INTERVAL: u64 = 1_000_000;
+/// Compact encoding of the easiest possible target (difficulty 1)
+pub const DIFFICULTY_1_BITS: u32 = 0x2100_ffff;
+
+/// Largest factor the target can move by in a single retarget
+const MAX_ADJUSTMENT_FACTOR: u64 = 4;
+
+/// A 256-bit Proof of Work target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Target(U256);
+
+impl Target {
+    /// Wrap a raw 256-bit value
+    pub fn new(value: U256) -> Self {
+        Target(value)
+    }
+
+    /// The easiest target, at difficulty 1
+    pub fn difficulty_1() -> Self {
+        Target::from_compact(DIFFICULTY_1_BITS).expect("difficulty 1 bits are valid")
+    }
+
+    /// Decode a compact `bits` value
+    ///
+    /// Returns `None` for negative or overflowing encodings.
+    pub fn from_compact(bits: u32) -> Option<Self> {
+        let exponent = bits >> 24;
+        let mantissa = bits & 0x007f_ffff;
+        if bits & 0x0080_0000 != 0 && mantissa != 0 {
+            return None;
+        }
+
+        let value = if exponent <= 3 {
+            U256::from_u64((mantissa >> (8 * (3 - exponent))) as u64)
+        } else {
+            let overflows = mantissa != 0
+                && (exponent > 34 || (mantissa > 0xff && exponent > 33) || (mantissa > 0xffff && exponent > 32));
+            if overflows {
+                return None;
+            }
+            U256::from_u64(mantissa as u64).shl(8 * (exponent - 3))
+        };
+        Some(Target(value))
+    }
+
+    /// Encode as compact `bits`, rounding the target down to the encodable precision
+    pub fn to_compact(&self) -> u32 {
+        let mut size = self.0.bits().div_ceil(8);
+        let mut mantissa = if size <= 3 {
+            (self.0.low_u64() << (8 * (3 - size))) as u32
+        } else {
+            self.0.shr(8 * (size - 3)).low_u64() as u32
+        };
+
+        // The sign bit of the mantissa must stay clear
+        if mantissa & 0x0080_0000 != 0 {
+            mantissa >>= 8;
+            size += 1;
+        }
+        mantissa | (size << 24)
+    }
+
+    /// Target requiring about `difficulty` hashes (a difficulty of 0 is treated as 1)
+    pub fn from_difficulty(difficulty: u64) -> Self {
+        Target(Self::difficulty_1().0.div_u64(difficulty.max(1)))
+    }
+
+    /// Target whose valid hashes start with at least `zero_bits` zero bits
+    pub fn from_leading_zeros(zero_bits: u32) -> Self {
+        let value = U256::MAX.shr(zero_bits);
+        // Round down so the compact form still guarantees the leading zeros
+        Target::from_compact(Target(value).to_compact()).expect("re-encoded target is valid")
+    }
+
+    /// Raw 256-bit value
+    pub fn value(&self) -> U256 {
+        self.0
+    }
+
+    /// Difficulty of this target relative to [`Target::difficulty_1`]
+    pub fn difficulty(&self) -> f64 {
+        if self.0.is_zero() {
+            return f64::INFINITY;
+        }
+        Self::difficulty_1().0.to_f64() / self.0.to_f64()
+    }
+
+    /// Expected number of hashes needed to meet this target, `2^256 / (target + 1)`
+    pub fn work(&self) -> U256 {
+        if self.0 == U256::MAX {
+            return U256::ONE;
+        }
+        // 2^256 / (t + 1) == (2^256 - 1 - t) / (t + 1) + 1
+        let divisor = self.0.checked_add(&U256::ONE).expect("target below U256::MAX");
+        self.0.not().div(&divisor).saturating_add(&U256::ONE)
+    }
+
+    /// Whether a 32-byte hash, read big-endian, is at or below this target
+    pub fn is_met_by(&self, hash: &[u8; 32]) -> bool {
+        U256::from_be_bytes(hash) <= self.0
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0.to_be_bytes()))
+    }
+}
+
+/// Compact bits for a target of the given difficulty
+pub fn bits_from_difficulty(difficulty: u64) -> u32 {
+    Target::from_difficulty(difficulty).to_compact()
+}
+
+/// Difficulty of compact bits (0 for invalid encodings)
+pub fn difficulty_from_bits(bits: u32) -> f64 {
+    Target::from_compact(bits).map_or(0.0, |target| target.difficulty())
+}
+
+/// Expected work of compact bits (0 for invalid encodings)
+pub fn work_from_bits(bits: u32) -> U256 {
+    Target::from_compact(bits).map_or(U256::ZERO, |target| target.work())
+}
+
+/// Whether the block at `height` is the first of a new difficulty period
+pub fn is_retarget_height(params: &ConsensusParams, height: u64) -> bool {
+    height > 0 && params.retarget_interval > 0 && height.is_multiple_of(params.retarget_interval)
+}
+
+/// Height of the block whose timestamp opens the window measured when
+/// retargeting at `height`
+pub fn window_start_height(params: &ConsensusParams, height: u64) -> u64 {
+    height.saturating_sub(params.retarget_interval.saturating_add(1))
+}
+
+/// Compact bits of the block following `parent`
+///
+/// `window_start` must be the ancestor at [`window_start_height`]; it is only
+/// consulted when the new block starts a difficulty period. The new target is
+/// the parent target scaled by `actual / expected` window time, with the ratio
+/// clamped to [1/4, 4] and the result never easier than the proof of work limit.
+pub fn next_bits(params: &ConsensusParams, parent: &BlockHeader, window_start: &BlockHeader) -> u32 {
+    let height = parent.block_height + 1;
+    if !is_retarget_height(params, height) {
+        return parent.bits;
+    }
+    let parent_target = match Target::from_compact(parent.bits) {
+        Some(target) => target,
+        None => return parent.bits,
+    };
+
+    let gaps = parent.block_height.saturating_sub(window_start.block_height);
+    let expected_ms = gaps
+        .saturating_mul(params.target_block_time_secs)
+        .saturating_mul(1000)
+        .max(1);
+    let actual_ms = (parent.timestamp - window_start.timestamp).num_milliseconds().max(0) as u64;
+    let actual_ms = actual_ms.clamp(
+        expected_ms / MAX_ADJUSTMENT_FACTOR,
+        expected_ms.saturating_mul(MAX_ADJUSTMENT_FACTOR),
+    );
+
+    let pow_limit = Target::from_compact(params.pow_limit_bits).unwrap_or_else(Target::difficulty_1);
+    // Large targets are scaled with their low 64 bits dropped, well below the
+    // precision kept by the compact encoding
+    let value = parent_target.value();
+    let scaled = match value.checked_mul_u64(actual_ms) {
+        Some(product) => Some(product.div_u64(expected_ms)),
+        None => value
+            .shr(64)
+            .checked_mul_u64(actual_ms)
+            .map(|product| product.div_u64(expected_ms))
+            .filter(|quotient| quotient.bits() <= 192)
+            .map(|quotient| quotient.shl(64)),
+    };
+    let scaled = scaled.map(Target::new).unwrap_or(pow_limit);
+    scaled.min(pow_limit).to_compact()
+}
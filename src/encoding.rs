@@ -0,0 +1,188 @@
+//! Canonical binary encoding for hashing, signing and the wire
+//!
+//! Integers are written little-endian at fixed width, timestamps as signed
+//! seconds since the Unix epoch followed by nanoseconds, and variable-length
+//! data is prefixed with its length as a `u32`. Keys, hashes, signatures and
+//! addresses are written as fixed-size byte arrays. Every value therefore has
+//! exactly one encoding and no two values share one; JSON is only a presentation
+//! format.
+//!
+//! Encoding cannot fail: every field type has a canonical form. Only decoding
+//! untrusted bytes returns errors.
+
+use crate::address::{Address, AddressError};
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+/// Reasons a value cannot be encoded or decoded
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EncodingError {
+    #[error("unexpected end of input")]
+    UnexpectedEnd,
+    #[error("{0} trailing bytes after the encoded value")]
+    TrailingBytes(usize),
+    #[error("string is not valid UTF-8")]
+    InvalidUtf8,
+    #[error("timestamp out of range")]
+    InvalidTimestamp,
+    #[error("invalid flag byte {0}")]
+    InvalidFlag(u8),
+    #[error("invalid address: {0}")]
+    InvalidAddress(#[from] AddressError),
+}
+
+/// Builder for a canonical encoding
+#[derive(Debug, Clone, Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    /// Create an empty encoder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a single byte
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.buf.push(value);
+        self
+    }
+
+    /// Write a `u32`
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Write a `u64`
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Write an `i64`
+    pub fn i64(&mut self, value: i64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Write a fixed-size array without a length prefix
+    pub fn fixed(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(bytes);
+        self
+    }
+
+    /// Write a `u32` length
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` exceeds `u32::MAX`, far beyond any valid transaction or block.
+    pub fn length(&mut self, len: usize) -> &mut Self {
+        self.u32(u32::try_from(len).expect("length fits in u32"))
+    }
+
+    /// Write length-prefixed bytes
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.length(bytes.len()).fixed(bytes)
+    }
+
+    /// Write a length-prefixed UTF-8 string
+    pub fn str(&mut self, value: &str) -> &mut Self {
+        self.bytes(value.as_bytes())
+    }
+
+    /// Write an address as its raw version byte and public key hash
+    pub fn address(&mut self, value: &Address) -> &mut Self {
+        self.fixed(&value.to_bytes())
+    }
+
+    /// Write a timestamp as seconds and nanoseconds
+    pub fn timestamp(&mut self, value: &DateTime<Utc>) -> &mut Self {
+        self.i64(value.timestamp()).u32(value.timestamp_subsec_nanos())
+    }
+
+    /// Finish and return the encoded bytes
+    pub fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+}
+
+/// Reader for a canonical encoding
+#[derive(Debug, Clone)]
+pub struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    /// Start decoding `data`
+    pub fn new(data: &'a [u8]) -> Self {
+        Decoder { data }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], EncodingError> {
+        if self.data.len() < len {
+            return Err(EncodingError::UnexpectedEnd);
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    /// Read a fixed-size array
+    pub fn fixed<const N: usize>(&mut self) -> Result<[u8; N], EncodingError> {
+        Ok(self.take(N)?.try_into().expect("slice of length N"))
+    }
+
+    /// Read a single byte
+    pub fn u8(&mut self) -> Result<u8, EncodingError> {
+        Ok(self.fixed::<1>()?[0])
+    }
+
+    /// Read a `u32`
+    pub fn u32(&mut self) -> Result<u32, EncodingError> {
+        Ok(u32::from_le_bytes(self.fixed()?))
+    }
+
+    /// Read a `u64`
+    pub fn u64(&mut self) -> Result<u64, EncodingError> {
+        Ok(u64::from_le_bytes(self.fixed()?))
+    }
+
+    /// Read an `i64`
+    pub fn i64(&mut self) -> Result<i64, EncodingError> {
+        Ok(i64::from_le_bytes(self.fixed()?))
+    }
+
+    /// Read length-prefixed bytes
+    pub fn bytes(&mut self) -> Result<&'a [u8], EncodingError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    /// Read a length-prefixed UTF-8 string
+    pub fn string(&mut self) -> Result<String, EncodingError> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| EncodingError::InvalidUtf8)
+    }
+
+    /// Read an address written by [`Encoder::address`]
+    pub fn address(&mut self) -> Result<Address, EncodingError> {
+        Ok(Address::from_bytes(self.fixed()?)?)
+    }
+
+    /// Read a timestamp written by [`Encoder::timestamp`]
+    pub fn timestamp(&mut self) -> Result<DateTime<Utc>, EncodingError> {
+        let seconds = self.i64()?;
+        let nanos = self.u32()?;
+        DateTime::from_timestamp(seconds, nanos).ok_or(EncodingError::InvalidTimestamp)
+    }
+
+    /// Check that the whole input was consumed
+    pub fn finish(self) -> Result<(), EncodingError> {
+        match self.data.len() {
+            0 => Ok(()),
+            trailing => Err(EncodingError::TrailingBytes(trailing)),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Error types for transaction and block validation and chain operations
+
+use crate::address::Address;
+use crate::primitives::{Hash32, KeyError};
+use crate::storage::StoreError;
+use chrono::{DateTime, Utc};
+use std::ops::Deref;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Reasons a transaction is rejected
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TransactionError {
+    #[error("invalid sender key: {0}")]
+    InvalidKey(#[from] KeyError),
+    #[error("transaction id mismatch (expected {expected}, found {actual})")]
+    TxnIdMismatch { expected: Hash32, actual: Hash32 },
+    #[error("signature verification failed")]
+    InvalidSignature,
+    #[error("insufficient funds in {address}: balance {balance}, required {required}")]
+    InsufficientFunds { address: Address, balance: u64, required: u64 },
+    #[error("amount overflow")]
+    AmountOverflow,
+    #[error("nonce {actual} already used (next expected nonce is {expected})")]
+    NonceTooLow { expected: u64, actual: u64 },
+    #[error("nonce {actual} skips ahead of the next expected nonce {expected}")]
+    NonceGap { expected: u64, actual: u64 },
+    #[error("transaction {0} is already in the chain")]
+    DuplicateTransaction(Hash32),
+    #[error("coinbase transaction outside the first position of a block")]
+    MisplacedCoinbase,
+    #[error("transaction is for chain {actual}, not {expected}")]
+    WrongChain { expected: Hash32, actual: Hash32 },
+    #[error("transaction {0} is already pending")]
+    AlreadyPending(Hash32),
+    #[error("fee {fee} is below the minimum of {minimum}")]
+    FeeTooLow { fee: u64, minimum: u64 },
+    #[error("replacement fee {fee} is below the required {required}")]
+    ReplacementFeeTooLow { fee: u64, required: u64 },
+    #[error("pending transaction was already replaced {limit} times")]
+    TooManyReplacements { limit: u32 },
+    #[error("pending pool is full and the fee rate is too low to evict anything")]
+    MempoolFull,
+    #[error("transaction timestamp {timestamp} is older than the pending pool accepts ({limit})")]
+    Expired { timestamp: DateTime<Utc>, limit: DateTime<Utc> },
+}
+
+/// Reasons a block is rejected
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BlockError {
+    #[error("block {height} does not start with a coinbase transaction")]
+    MissingCoinbase { height: u64 },
+    #[error("coinbase nonce {actual} must equal the block height {expected}")]
+    InvalidCoinbaseNonce { expected: u64, actual: u64 },
+    #[error("coinbase must not pay a fee (found {0})")]
+    CoinbaseFee(u64),
+    #[error("coinbase must not carry a signature")]
+    SignedCoinbase,
+    #[error("invalid coinbase: {0}")]
+    InvalidCoinbase(TransactionError),
+    #[error("coinbase pays {actual} but exactly {expected} is required")]
+    InvalidCoinbaseAmount { expected: u64, actual: u64 },
+    #[error("total fees overflow")]
+    FeeOverflow,
+    #[error("invalid transaction: {0}")]
+    InvalidTransaction(#[from] TransactionError),
+    #[error("block {0} is already known")]
+    DuplicateBlock(Hash32),
+    #[error("parent block {0} is unknown")]
+    UnknownParent(Hash32),
+    #[error("parent hash mismatch (expected {expected}, found {actual})")]
+    ParentHashMismatch { expected: Hash32, actual: Hash32 },
+    #[error("genesis block must not contain transactions")]
+    GenesisWithTransactions,
+    #[error("block height {actual} does not follow its parent (expected {expected})")]
+    InvalidHeight { expected: u64, actual: u64 },
+    #[error("block target bits {actual:#010x} differ from the required {expected:#010x}")]
+    UnexpectedBits { expected: u32, actual: u32 },
+    #[error("block hash {0} does not meet its proof of work target")]
+    InsufficientProofOfWork(Hash32),
+    #[error("block declares {declared} transactions but contains {actual}")]
+    TransactionCountMismatch { declared: u32, actual: usize },
+    #[error("block contains {count} transactions, more than the limit of {limit}")]
+    TooManyTransactions { count: usize, limit: u32 },
+    #[error("block is {size} bytes, more than the limit of {limit}")]
+    BlockTooLarge { size: usize, limit: u64 },
+    #[error("merkle root mismatch (expected {expected}, found {actual})")]
+    MerkleRootMismatch { expected: Hash32, actual: Hash32 },
+    #[error("block timestamp {timestamp} is not after the median time past {median_time_past}")]
+    TimestampNotAfterMedian { timestamp: DateTime<Utc>, median_time_past: DateTime<Utc> },
+    #[error("block timestamp {timestamp} is too far in the future (latest accepted {limit})")]
+    TimestampTooFarInFuture { timestamp: DateTime<Utc>, limit: DateTime<Utc> },
+    #[error("block could not be stored: {0}")]
+    Storage(#[from] SharedStoreError),
+}
+
+/// Reasons a chain operation fails
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ChainError {
+    #[error("no pending transactions to mine")]
+    NoPendingTransactions,
+    #[error("invalid block: {0}")]
+    InvalidBlock(#[from] BlockError),
+    #[error("block could not be stored: {0}")]
+    Storage(#[from] SharedStoreError),
+}
+
+/// A [`StoreError`] behind an [`Arc`], so the errors carrying it stay cloneable
+///
+/// Two shared errors are equal only if they are the same failure.
+#[derive(Debug, Clone, Error)]
+#[error(transparent)]
+pub struct SharedStoreError(Arc<StoreError>);
+
+impl PartialEq for SharedStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for SharedStoreError {}
+
+impl Deref for SharedStoreError {
+    type Target = StoreError;
+
+    fn deref(&self) -> &StoreError {
+        &self.0
+    }
+}
+
+impl From<StoreError> for SharedStoreError {
+    fn from(error: StoreError) -> Self {
+        SharedStoreError(Arc::new(error))
+    }
+}
+
+impl From<StoreError> for BlockError {
+    fn from(error: StoreError) -> Self {
+        BlockError::Storage(error.into())
+    }
+}
+
+impl From<StoreError> for ChainError {
+    fn from(error: StoreError) -> Self {
+        ChainError::Storage(error.into())
+    }
+}
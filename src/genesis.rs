@@ -0,0 +1,259 @@
+//! Genesis block configuration
+//!
+//! Nodes can only share a chain if they start from the same genesis block, so
+//! a network's genesis block is never mined at start-up. It is built from a
+//! [`GenesisConfig`]: a chain name, a fixed timestamp and nonce, the consensus
+//! rules (whose `initial_bits` is the genesis difficulty) and the balances
+//! credited before the first block. The hash of that block is the chain ID.
+//!
+//! The genesis block has no transactions; its merkle root instead commits to the
+//! consensus rules and the allocations (see [`genesis_commitment`]), so networks
+//! that differ in either never share a chain ID.
+//!
+//! Configurations for mainnet, testnet and regtest are built in; others can be
+//! loaded from JSON or TOML files. In TOML the timestamp is written as a quoted
+//! RFC 3339 string, and in both formats omitted consensus rules take their
+//! default values.
+
+use crate::address::Address;
+use crate::consensus::{
+    ConsensusParams, MAX_BLOCK_SIZE_LIMIT, MAX_BLOCK_TRANSACTIONS_LIMIT, MAX_MIN_FEE_PER_BYTE,
+    MAX_MIN_TRANSACTION_FEE, MIN_BLOCK_SIZE_LIMIT,
+};
+use crate::difficulty::{self, MAX_RETARGET_INTERVAL, MAX_TARGET_BLOCK_TIME_SECS};
+use crate::encoding::Encoder;
+use crate::error::{BlockError, TransactionError};
+use crate::primitives::Hash32;
+use crate::state::AccountState;
+use crate::{Block, GENESIS_PARENT_HASH};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Errors produced when loading or checking a genesis configuration
+#[derive(Debug, Error)]
+pub enum GenesisError {
+    #[error("genesis file I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid JSON genesis file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid TOML genesis file: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("genesis file {0} is neither .json nor .toml")]
+    UnsupportedFormat(PathBuf),
+    #[error("invalid genesis block: {0}")]
+    InvalidBlock(BlockError),
+    #[error("invalid genesis allocations: {0}")]
+    InvalidAllocations(TransactionError),
+    #[error("genesis block mismatch (expected {expected}, found {actual})")]
+    Mismatch { expected: Hash32, actual: Hash32 },
+    #[error("consensus parameter {name} is {value}, outside {min}..={max}")]
+    ParamOutOfRange { name: &'static str, value: u64, min: u64, max: u64 },
+}
+
+/// Everything that determines a chain's genesis block and initial state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisConfig {
+    /// Human-readable name of the chain, e.g. `mainnet`
+    pub chain_name: String,
+    /// Timestamp of the genesis block
+    pub timestamp: DateTime<Utc>,
+    /// Proof of Work nonce of the genesis block
+    pub nonce: u64,
+    /// Balances credited before the first block is applied
+    #[serde(default)]
+    pub allocations: BTreeMap<Address, u64>,
+    /// Consensus rules of the chain; `initial_bits` is the genesis target
+    #[serde(default)]
+    pub params: ConsensusParams,
+}
+
+impl GenesisConfig {
+    /// The main network: default rules, no allocations
+    pub fn mainnet() -> Self {
+        GenesisConfig {
+            chain_name: "mainnet".to_string(),
+            timestamp: DateTime::from_timestamp(1_735_689_600, 0).expect("valid timestamp"),
+            nonce: 60_550,
+            allocations: BTreeMap::new(),
+            params: ConsensusParams::default(),
+        }
+    }
+
+    /// The public test network: default rules at a lower starting difficulty
+    pub fn testnet() -> Self {
+        GenesisConfig {
+            chain_name: "testnet".to_string(),
+            timestamp: DateTime::from_timestamp(1_735_776_000, 0).expect("valid timestamp"),
+            nonce: 80,
+            allocations: BTreeMap::new(),
+            params: ConsensusParams::with_difficulty(1 << 10),
+        }
+    }
+
+    /// Local regression testing: minimum difficulty and no retargeting, so
+    /// blocks can be mined instantly
+    pub fn regtest() -> Self {
+        GenesisConfig {
+            chain_name: "regtest".to_string(),
+            timestamp: DateTime::from_timestamp(1_735_862_400, 0).expect("valid timestamp"),
+            nonce: 0,
+            allocations: BTreeMap::new(),
+            params: ConsensusParams {
+                initial_bits: difficulty::DIFFICULTY_1_BITS,
+                retarget_interval: 0,
+                ..ConsensusParams::default()
+            },
+        }
+    }
+
+    /// Built-in configuration called `name` (`mainnet`, `testnet` or `regtest`)
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "mainnet" => Some(Self::mainnet()),
+            "testnet" => Some(Self::testnet()),
+            "regtest" => Some(Self::regtest()),
+            _ => None,
+        }
+    }
+
+    /// Parse a configuration from JSON
+    pub fn from_json(json: &str) -> Result<Self, GenesisError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Parse a configuration from TOML
+    pub fn from_toml(toml: &str) -> Result<Self, GenesisError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Load a configuration from a `.json` or `.toml` file and validate it
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GenesisError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        let config = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&contents)?,
+            Some("toml") => Self::from_toml(&contents)?,
+            _ => return Err(GenesisError::UnsupportedFormat(path.to_path_buf())),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The genesis block described by this configuration
+    pub fn block(&self) -> Block {
+        let mut block = genesis_block(&self.params, &self.allocation_list());
+        block.header.timestamp = self.timestamp;
+        block.header.nonce = self.nonce;
+        block
+    }
+
+    /// Hash of the genesis block, which is the chain ID
+    pub fn hash(&self) -> Hash32 {
+        self.block().calculate_hash()
+    }
+
+    /// Allocations as a list of address and amount pairs, ordered by address
+    pub fn allocation_list(&self) -> Vec<(Address, u64)> {
+        self.allocations.iter().map(|(address, amount)| (*address, *amount)).collect()
+    }
+
+    /// Check that the genesis block meets its own target, the allocations can be
+    /// credited and the consensus parameters are within bounds
+    ///
+    /// The subsidy must be positive and no larger than the supply cap, and the
+    /// halving interval at least one block.
+    pub fn validate(&self) -> Result<(), GenesisError> {
+        let block = self.block();
+        if !block.header.meets_difficulty_target() {
+            return Err(GenesisError::InvalidBlock(BlockError::InsufficientProofOfWork(block.calculate_hash())));
+        }
+        AccountState::with_allocations(&self.allocation_list()).map_err(GenesisError::InvalidAllocations)?;
+        check_range("target_block_time_secs", self.params.target_block_time_secs, 1, MAX_TARGET_BLOCK_TIME_SECS)?;
+        check_range("retarget_interval", self.params.retarget_interval, 0, MAX_RETARGET_INTERVAL)?;
+        check_range("max_block_size", self.params.max_block_size, MIN_BLOCK_SIZE_LIMIT, MAX_BLOCK_SIZE_LIMIT)?;
+        check_range(
+            "max_block_transactions",
+            self.params.max_block_transactions as u64,
+            1,
+            MAX_BLOCK_TRANSACTIONS_LIMIT as u64,
+        )?;
+        let monetary = &self.params.monetary;
+        check_range("max_supply", monetary.max_supply, 1, u64::MAX)?;
+        check_range("initial_subsidy", monetary.initial_subsidy, 1, monetary.max_supply)?;
+        check_range("halving_interval", monetary.halving_interval, 1, u64::MAX)?;
+        check_range("min_transaction_fee", self.params.min_transaction_fee, 0, MAX_MIN_TRANSACTION_FEE)?;
+        check_range("min_fee_per_byte", self.params.min_fee_per_byte, 0, MAX_MIN_FEE_PER_BYTE)?;
+        Ok(())
+    }
+
+    /// Check that `block` is the genesis block of this configuration
+    pub fn check_block(&self, block: &Block) -> Result<(), GenesisError> {
+        let expected = self.hash();
+        let actual = block.calculate_hash();
+        if actual != expected {
+            return Err(GenesisError::Mismatch { expected, actual });
+        }
+        Ok(())
+    }
+
+    /// Search for a nonce, starting from the current one, that makes the genesis
+    /// block meet its target; used when setting up a new network
+    pub fn with_mined_nonce(mut self) -> Self {
+        let mut block = self.block();
+        block.mine_block();
+        self.timestamp = block.header.timestamp;
+        self.nonce = block.header.nonce;
+        self
+    }
+}
+
+/// Check that a consensus parameter lies within `min..=max`
+fn check_range(name: &'static str, value: u64, min: u64, max: u64) -> Result<(), GenesisError> {
+    if value < min || value > max {
+        return Err(GenesisError::ParamOutOfRange { name, value, min, max });
+    }
+    Ok(())
+}
+
+/// Domain separation tag prefixed to the genesis commitment
+const GENESIS_COMMITMENT_DOMAIN: &[u8] = b"i-protocol genesis commitment v1";
+
+/// Hash committed to as the merkle root of a genesis block: every consensus
+/// rule and every allocation, in address order
+pub fn genesis_commitment(params: &ConsensusParams, allocations: &[(Address, u64)]) -> Hash32 {
+    let mut encoder = Encoder::new();
+    encoder
+        .bytes(GENESIS_COMMITMENT_DOMAIN)
+        .u32(params.initial_bits)
+        .u32(params.pow_limit_bits)
+        .u64(params.target_block_time_secs)
+        .u64(params.retarget_interval)
+        .u64(params.median_time_span)
+        .u64(params.max_future_drift_secs)
+        .u64(params.monetary.initial_subsidy)
+        .u64(params.monetary.halving_interval)
+        .u64(params.monetary.max_supply)
+        .u64(params.max_block_size)
+        .u32(params.max_block_transactions)
+        .u64(params.min_transaction_fee)
+        .u64(params.min_fee_per_byte)
+        .length(allocations.len());
+    let mut allocations = allocations.to_vec();
+    allocations.sort();
+    for (address, amount) in &allocations {
+        encoder.address(address).u64(*amount);
+    }
+    Hash32::digest(&encoder.finish())
+}
+
+/// Unmined genesis block for `params` and `allocations`, stamped with the Unix epoch
+pub fn genesis_block(params: &ConsensusParams, allocations: &[(Address, u64)]) -> Block {
+    let mut block = Block::new(0, GENESIS_PARENT_HASH, Vec::new(), params.initial_bits);
+    block.header.merkle_root = genesis_commitment(params, allocations);
+    block
+}
@@ -1,32 +1,85 @@
-use blake3::Hasher;
 use chrono::{DateTime, Utc};
+use encoding::{Decoder, Encoder};
 use ed25519_dalek::{VerifyingKey, Signature, Verifier};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fmt;
-use std::collections::VecDeque;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
 
-/// Fixed transaction fee for MVP (0.001 I tokens)
+pub mod address;
+pub mod block_tree;
+pub mod clock;
+pub mod consensus;
+pub mod difficulty;
+pub mod encoding;
+pub mod error;
+pub mod genesis;
+pub mod mempool;
+pub mod merkle;
+pub mod monetary;
+pub mod primitives;
+pub mod state;
+pub mod storage;
+pub mod template;
+pub mod uint;
+pub mod wallet;
+
+pub use address::{Address, AddressError};
+pub use block_tree::{BlockNode, BlockTree};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use consensus::ConsensusParams;
+pub use difficulty::Target;
+pub use encoding::EncodingError;
+pub use error::{BlockError, ChainError, SharedStoreError, TransactionError};
+pub use genesis::{GenesisConfig, GenesisError};
+pub use mempool::{Mempool, MempoolConfig, MempoolEntry};
+pub use merkle::{verify_merkle_proof, MerkleProof};
+pub use monetary::MonetaryPolicy;
+pub use primitives::{Hash32, HexError, KeyError, PublicKey, Sig64};
+pub use state::{Account, AccountState, StateUndo};
+pub use storage::{BlockStore, ChainStore, FileStore, MemoryStore, StateBatch, StoreError};
+pub use template::{BlockTemplate, BlockTemplateBuilder};
+pub use uint::U256;
+pub use wallet::{Wallet, WalletError};
+
+/// Default transaction fee (0.01 I tokens), well above the minimum relay fee
 pub const TRANSACTION_FEE: u64 = 1_000_000; // Using satoshi-like precision (1 I = 100_000_000 units)
 
+/// Number of smallest units in one I token
+pub const COIN: u64 = 100_000_000;
+
+/// Placeholder `from` value marking a coinbase transaction (no sender)
+pub const COINBASE_SENDER: PublicKey = PublicKey::from_bytes([0; 32]);
+
+/// Parent hash recorded in the genesis block
+pub const GENESIS_PARENT_HASH: Hash32 = Hash32::ZERO;
+
+/// Domain separation tag prefixed to every transaction signing message
+pub const TRANSACTION_SIGNING_DOMAIN: &[u8] = b"i-protocol/transaction/v1";
+
 /// Transaction structure representing value transfer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     /// Transaction ID (Blake3 hash of transaction data)
-    pub txn_id: String,
-    /// Sender's Ed25519 public key (32 bytes)
-    pub from: String,
-    /// Recipient's address derived from public key
-    pub to: String,
+    pub txn_id: Hash32,
+    /// ID of the chain this transaction is valid on (see [`Blockchain::chain_id`])
+    pub chain_id: Hash32,
+    /// Sender's Ed25519 public key
+    pub from: PublicKey,
+    /// Recipient's address derived from public key (see [`Address`])
+    pub to: Address,
     /// Amount to transfer (in smallest units, like satoshis)
     pub amount: u64,
-    /// Transaction fee (fixed at 0.001 I for MVP)
+    /// Fee paid to the miner, chosen by the sender; higher fees per byte are mined first
     pub fee: u64,
     /// Account nonce for replay protection
     pub nonce: u64,
     /// Transaction creation timestamp
     pub timestamp: DateTime<Utc>,
-    /// Ed25519 signature (64 bytes)
-    pub signature: String,
+    /// Ed25519 signature (`None` until signed, and always for a coinbase)
+    pub signature: Option<Sig64>,
 }
 
 /// Block header containing metadata and PoW solution
@@ -35,13 +88,13 @@ pub struct BlockHeader {
     /// Block number in the chain (0 = genesis block)
     pub block_height: u64,
     /// Hash of the previous block
-    pub parent_hash: String,
+    pub parent_hash: Hash32,
     /// Merkle root of all transactions in this block
-    pub merkle_root: String,
+    pub merkle_root: Hash32,
     /// Block creation timestamp
     pub timestamp: DateTime<Utc>,
-    /// Proof of Work difficulty target
-    pub difficulty: u32,
+    /// Proof of Work target in compact encoding (see [`difficulty::Target`])
+    pub bits: u32,
     /// Proof of Work nonce solution
     pub nonce: u64,
 }
@@ -58,21 +111,24 @@ pub struct Block {
 }
 
 impl Transaction {
-    /// Create a new transaction
+    /// Create a new transaction for the chain `chain_id`
     pub fn new(
-        from: String,
-        to: String,
+        chain_id: Hash32,
+        from: PublicKey,
+        to: Address,
         amount: u64,
+        fee: u64,
         nonce: u64,
-        signature: String,
+        signature: Option<Sig64>,
     ) -> Self {
         let timestamp = Utc::now();
         let mut tx = Transaction {
-            txn_id: String::new(),
+            txn_id: Hash32::ZERO,
+            chain_id,
             from,
             to,
             amount,
-            fee: TRANSACTION_FEE,
+            fee,
             nonce,
             timestamp,
             signature,
@@ -83,47 +139,173 @@ impl Transaction {
         tx
     }
 
+    /// Create the coinbase transaction paying `amount` to the miner of the block at `block_height`
+    ///
+    /// The coinbase has no sender, fee or signature; its nonce is the block height
+    /// so that every coinbase has a distinct transaction ID.
+    pub fn coinbase(chain_id: Hash32, miner: &Address, amount: u64, block_height: u64) -> Self {
+        let mut tx = Transaction {
+            txn_id: Hash32::ZERO,
+            chain_id,
+            from: COINBASE_SENDER,
+            to: *miner,
+            amount,
+            fee: 0,
+            nonce: block_height,
+            timestamp: Utc::now(),
+            signature: None,
+        };
+        tx.txn_id = tx.calculate_hash();
+        tx
+    }
+
+    /// Whether this is a coinbase transaction
+    pub fn is_coinbase(&self) -> bool {
+        self.from == COINBASE_SENDER
+    }
+
     /// Calculate Blake3 hash of transaction data
-    pub fn calculate_hash(&self) -> String {
-        let mut hasher = Hasher::new();
-        
-        // Hash all fields except txn_id and signature
-        hasher.update(self.from.as_bytes());
-        hasher.update(self.to.as_bytes());
-        hasher.update(&self.amount.to_le_bytes());
-        hasher.update(&self.fee.to_le_bytes());
-        hasher.update(&self.nonce.to_le_bytes());
-        hasher.update(self.timestamp.to_rfc3339().as_bytes());
-        
-        hex::encode(hasher.finalize().as_bytes())
+    ///
+    /// The hash covers the canonical signing message (every field except `txn_id`
+    /// and `signature`).
+    pub fn calculate_hash(&self) -> Hash32 {
+        Hash32::digest(&self.signing_message())
     }
 
     /// Verify the Ed25519 signature of this transaction
-    pub fn verify_signature(&self, public_key: &VerifyingKey) -> Result<bool, Box<dyn std::error::Error>> {
-        let message = self.get_signing_message();
-        let signature_bytes = hex::decode(&self.signature)?;
-        
-        // Convert Vec<u8> to [u8; 64]
-        if signature_bytes.len() != 64 {
-            return Ok(false);
+    ///
+    /// An unsigned transaction never verifies.
+    pub fn verify_signature(&self, public_key: &VerifyingKey) -> bool {
+        match &self.signature {
+            Some(signature) => {
+                let signature = Signature::from_bytes(signature.as_bytes());
+                public_key.verify(&self.signing_message(), &signature).is_ok()
+            }
+            None => false,
         }
-        let mut sig_array = [0u8; 64];
-        sig_array.copy_from_slice(&signature_bytes);
-        
-        let signature = Signature::from_bytes(&sig_array);
-        
-        match public_key.verify(message.as_bytes(), &signature) {
-            Ok(()) => Ok(true),
-            Err(_) => Ok(false),
+    }
+
+    /// Address of the sender, derived from the `from` public key
+    pub fn sender_address(&self) -> Result<Address, KeyError> {
+        Ok(Address::from_public_key(&self.from.verifying_key()?))
+    }
+
+    /// Check that this transaction was created for the chain `chain_id`
+    pub fn check_chain(&self, chain_id: &Hash32) -> Result<(), TransactionError> {
+        if self.chain_id != *chain_id {
+            return Err(TransactionError::WrongChain {
+                expected: *chain_id,
+                actual: self.chain_id,
+            });
         }
+        Ok(())
     }
 
-    /// Get the message that should be signed
-    fn get_signing_message(&self) -> String {
-        format!(
-            "{}{}{}{}{}{}",
-            self.from, self.to, self.amount, self.fee, self.nonce, self.timestamp.to_rfc3339()
-        )
+    /// Fully verify this transaction: sender key, transaction ID and signature
+    ///
+    /// Coinbase transactions are unsigned and are validated as part of their block instead.
+    pub fn verify(&self) -> Result<(), TransactionError> {
+        if self.is_coinbase() {
+            return Err(TransactionError::MisplacedCoinbase);
+        }
+        let public_key = self.from.verifying_key()?;
+
+        let expected = self.calculate_hash();
+        if self.txn_id != expected {
+            return Err(TransactionError::TxnIdMismatch {
+                expected,
+                actual: self.txn_id,
+            });
+        }
+
+        if self.verify_signature(&public_key) {
+            Ok(())
+        } else {
+            Err(TransactionError::InvalidSignature)
+        }
+    }
+
+    /// The message that is hashed into the transaction ID and signed: the
+    /// [`TRANSACTION_SIGNING_DOMAIN`] tag followed by the canonical encoding of
+    /// every field except `txn_id` and `signature`
+    ///
+    /// The tag keeps transaction signatures from being valid in any other signing
+    /// context, and the chain ID keeps them from being replayed on another chain.
+    pub fn signing_message(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.bytes(TRANSACTION_SIGNING_DOMAIN);
+        self.encode_body(&mut encoder);
+        encoder.finish()
+    }
+
+    /// Canonical wire encoding: every field except `txn_id`, followed by the signature
+    ///
+    /// The domain tag and the transaction ID are not transmitted; the ID is
+    /// recomputed on decoding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        self.encode(&mut encoder);
+        encoder.finish()
+    }
+
+    /// Length of the wire encoding in bytes
+    pub fn size(&self) -> usize {
+        self.to_bytes().len()
+    }
+
+    /// Decode a transaction from its wire encoding
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EncodingError> {
+        let mut decoder = Decoder::new(bytes);
+        let tx = Self::decode(&mut decoder)?;
+        decoder.finish()?;
+        Ok(tx)
+    }
+
+    fn encode_body(&self, encoder: &mut Encoder) {
+        encoder
+            .fixed(self.chain_id.as_bytes())
+            .fixed(self.from.as_bytes())
+            .address(&self.to)
+            .u64(self.amount)
+            .u64(self.fee)
+            .u64(self.nonce)
+            .timestamp(&self.timestamp);
+    }
+
+    fn encode(&self, encoder: &mut Encoder) {
+        self.encode_body(encoder);
+        match &self.signature {
+            Some(signature) => encoder.u8(1).fixed(signature.as_bytes()),
+            None => encoder.u8(0),
+        };
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, EncodingError> {
+        let chain_id = Hash32::from_bytes(decoder.fixed()?);
+        let from = PublicKey::from_bytes(decoder.fixed()?);
+        let to = decoder.address()?;
+        let amount = decoder.u64()?;
+        let fee = decoder.u64()?;
+        let nonce = decoder.u64()?;
+        let timestamp = decoder.timestamp()?;
+        let signature = match decoder.u8()? {
+            0 => None,
+            1 => Some(Sig64::from_bytes(decoder.fixed()?)),
+            flag => return Err(EncodingError::InvalidFlag(flag)),
+        };
+        let mut tx = Transaction {
+            txn_id: Hash32::ZERO,
+            chain_id,
+            from,
+            to,
+            amount,
+            fee,
+            nonce,
+            timestamp,
+            signature,
+        };
+        tx.txn_id = tx.calculate_hash();
+        Ok(tx)
     }
 }
 
@@ -131,45 +313,79 @@ impl BlockHeader {
     /// Create a new block header
     pub fn new(
         block_height: u64,
-        parent_hash: String,
-        merkle_root: String,
-        difficulty: u32,
+        parent_hash: Hash32,
+        merkle_root: Hash32,
+        bits: u32,
     ) -> Self {
         BlockHeader {
             block_height,
             parent_hash,
             merkle_root,
             timestamp: Utc::now(),
-            difficulty,
+            bits,
             nonce: 0,
         }
     }
 
-    /// Calculate Blake3 hash of block header
-    pub fn calculate_hash(&self) -> String {
-        let mut hasher = Hasher::new();
-        
-        hasher.update(&self.block_height.to_le_bytes());
-        hasher.update(self.parent_hash.as_bytes());
-        hasher.update(self.merkle_root.as_bytes());
-        hasher.update(self.timestamp.to_rfc3339().as_bytes());
-        hasher.update(&self.difficulty.to_le_bytes());
-        hasher.update(&self.nonce.to_le_bytes());
-        
-        hex::encode(hasher.finalize().as_bytes())
+    /// Calculate Blake3 hash of the canonical header encoding, the value
+    /// compared against the target
+    pub fn calculate_hash(&self) -> Hash32 {
+        Hash32::digest(&self.to_bytes())
+    }
+
+    /// Canonical encoding of the header, used for hashing and on the wire
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        self.encode(&mut encoder);
+        encoder.finish()
+    }
+
+    /// Decode a header from its canonical encoding
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EncodingError> {
+        let mut decoder = Decoder::new(bytes);
+        let header = Self::decode(&mut decoder)?;
+        decoder.finish()?;
+        Ok(header)
+    }
+
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder
+            .u64(self.block_height)
+            .fixed(self.parent_hash.as_bytes())
+            .fixed(self.merkle_root.as_bytes())
+            .timestamp(&self.timestamp)
+            .u32(self.bits)
+            .u64(self.nonce);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self, EncodingError> {
+        Ok(BlockHeader {
+            block_height: decoder.u64()?,
+            parent_hash: Hash32::from_bytes(decoder.fixed()?),
+            merkle_root: Hash32::from_bytes(decoder.fixed()?),
+            timestamp: decoder.timestamp()?,
+            bits: decoder.u32()?,
+            nonce: decoder.u64()?,
+        })
+    }
+
+    /// Proof of Work target decoded from `bits` (`None` if the encoding is invalid)
+    pub fn target(&self) -> Option<Target> {
+        Target::from_compact(self.bits)
+    }
+
+    /// Expected number of hashes needed to produce this header
+    pub fn work(&self) -> U256 {
+        difficulty::work_from_bits(self.bits)
     }
 
     /// Check if the block header meets the difficulty target
     pub fn meets_difficulty_target(&self) -> bool {
-        let hash = self.calculate_hash();
-        let required_zeros = self.difficulty;
-        
-        // Count leading zeros in hex representation
-        let leading_zeros = hash.chars()
-            .take_while(|&c| c == '0')
-            .count() as u32;
-            
-        leading_zeros >= required_zeros
+        // The hash, read as a big-endian integer, must not exceed the target
+        match self.target() {
+            Some(target) => target.is_met_by(self.calculate_hash().as_bytes()),
+            None => false,
+        }
     }
 }
 
@@ -177,12 +393,12 @@ impl Block {
     /// Create a new block
     pub fn new(
         block_height: u64,
-        parent_hash: String,
+        parent_hash: Hash32,
         transactions: Vec<Transaction>,
-        difficulty: u32,
+        bits: u32,
     ) -> Self {
         let merkle_root = Self::calculate_merkle_root(&transactions);
-        let header = BlockHeader::new(block_height, parent_hash, merkle_root, difficulty);
+        let header = BlockHeader::new(block_height, parent_hash, merkle_root, bits);
         let transaction_count = transactions.len() as u32;
         
         Block {
@@ -193,26 +409,83 @@ impl Block {
     }
 
     /// Calculate the Blake3 hash of this block
-    pub fn calculate_hash(&self) -> String {
+    pub fn calculate_hash(&self) -> Hash32 {
         self.header.calculate_hash()
     }
 
-    /// Calculate merkle root of transactions (simplified version for MVP)
-    fn calculate_merkle_root(transactions: &[Transaction]) -> String {
-        if transactions.is_empty() {
-            return "0".repeat(64); // Empty merkle root
+    /// Calculate the Merkle root of transactions (see [`merkle`])
+    fn calculate_merkle_root(transactions: &[Transaction]) -> Hash32 {
+        let txn_ids: Vec<Hash32> = transactions.iter().map(|tx| tx.txn_id).collect();
+        merkle::merkle_root(&txn_ids)
+    }
+
+    /// Build an inclusion proof for the transaction with the given ID
+    pub fn merkle_proof(&self, txn_id: &Hash32) -> Option<MerkleProof> {
+        let index = self.transactions.iter().position(|tx| tx.txn_id == *txn_id)?;
+        let txn_ids: Vec<Hash32> = self.transactions.iter().map(|tx| tx.txn_id).collect();
+        merkle::build_proof(&txn_ids, index)
+    }
+
+    /// Canonical wire encoding: the header, the number of transactions and
+    /// each transaction in order
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        self.header.encode(&mut encoder);
+        encoder.length(self.transactions.len());
+        for tx in &self.transactions {
+            tx.encode(&mut encoder);
         }
+        encoder.finish()
+    }
+
+    /// Length of the wire encoding in bytes
+    pub fn size(&self) -> usize {
+        self.to_bytes().len()
+    }
+
+    /// Decode a block from its wire encoding
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EncodingError> {
+        let mut decoder = Decoder::new(bytes);
+        let header = BlockHeader::decode(&mut decoder)?;
+        let transaction_count = decoder.u32()?;
+        let transactions = (0..transaction_count)
+            .map(|_| Transaction::decode(&mut decoder))
+            .collect::<Result<Vec<_>, _>>()?;
+        decoder.finish()?;
+        Ok(Block {
+            header,
+            transaction_count,
+            transactions,
+        })
+    }
 
-        let mut hasher = Hasher::new();
-        for tx in transactions {
-            hasher.update(tx.txn_id.as_bytes());
+    /// Context-free checks: the declared transaction count, the merkle root and the
+    /// proof of work against the header's own target
+    ///
+    /// The merkle root of a genesis block commits to the chain's configuration
+    /// rather than its (empty) transaction list, so it is left to the chain to check.
+    pub fn check_structure(&self) -> Result<(), BlockError> {
+        if self.transaction_count as usize != self.transactions.len() {
+            return Err(BlockError::TransactionCountMismatch {
+                declared: self.transaction_count,
+                actual: self.transactions.len(),
+            });
         }
-        
-        hex::encode(hasher.finalize().as_bytes())
+        let merkle_root = Self::calculate_merkle_root(&self.transactions);
+        if self.header.block_height != 0 && self.header.merkle_root != merkle_root {
+            return Err(BlockError::MerkleRootMismatch {
+                expected: merkle_root,
+                actual: self.header.merkle_root,
+            });
+        }
+        if !self.header.meets_difficulty_target() {
+            return Err(BlockError::InsufficientProofOfWork(self.calculate_hash()));
+        }
+        Ok(())
     }
 
     /// Mine this block by finding a valid nonce
-    pub fn mine_block(&mut self) -> String {
+    pub fn mine_block(&mut self) -> Hash32 {
         println!("Mining block at height {}...", self.header.block_height);
         
         loop {
@@ -233,7 +506,7 @@ impl Block {
             }
             
             // Progress indicator every 100K attempts
-            if self.header.nonce % 100_000 == 0 {
+            if self.header.nonce.is_multiple_of(100_000) {
                 println!("Mining... nonce: {}", self.header.nonce);
             }
         }
@@ -245,9 +518,9 @@ impl fmt::Display for Transaction {
         write!(
             f,
             "Transaction {{ ID: {}, From: {}..., To: {}..., Amount: {}, Fee: {}, Nonce: {} }}",
-            &self.txn_id[..8],
-            &self.from[..8],
-            &self.to[..8],
+            &self.txn_id.to_string()[..8],
+            &self.from.to_string()[..8],
+            &self.to.to_string()[..8],
             self.amount,
             self.fee,
             self.nonce
@@ -259,46 +532,389 @@ impl fmt::Display for Block {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Block {{ Height: {}, Hash: {}, Transactions: {}, Difficulty: {}, Nonce: {} }}",
+            "Block {{ Height: {}, Hash: {}, Transactions: {}, Bits: {:#010x}, Nonce: {} }}",
             self.header.block_height,
-            &self.calculate_hash()[..16],
+            &self.calculate_hash().to_string()[..16],
             self.transaction_count,
-            self.header.difficulty,
+            self.header.bits,
             self.header.nonce
         )
     }
 }
 
+/// Outcome of adding a block to the chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockStatus {
+    /// The block was connected on top of the previous tip
+    ExtendedTip,
+    /// The block was stored on a branch with no more cumulative work than the active chain
+    SideBranch,
+    /// The block's branch overtook the active chain, which was switched over to it
+    Reorganized {
+        /// Blocks removed from the top of the previous active chain
+        disconnected: usize,
+        /// Blocks of the new branch connected in their place
+        connected: usize,
+    },
+    /// The parent is unknown; the block is held until the parent arrives
+    Orphan,
+}
+
+/// First invalid block found by [`Blockchain::validate_chain`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainFault {
+    /// Height of the block in the active chain
+    pub height: u64,
+    /// Hash of the offending block
+    pub hash: Hash32,
+    /// Why the block is invalid
+    pub reason: BlockError,
+}
+
+/// Outcome of validating the whole active chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainReport {
+    /// Number of blocks that passed validation before the first fault
+    pub blocks_checked: usize,
+    /// The first invalid block, if any
+    pub first_invalid: Option<ChainFault>,
+}
+
+impl ChainReport {
+    /// Whether every block of the chain is valid
+    pub fn is_valid(&self) -> bool {
+        self.first_invalid.is_none()
+    }
+}
+
+impl fmt::Display for ChainReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.first_invalid {
+            None => write!(f, "valid chain of {} blocks", self.blocks_checked),
+            Some(fault) => write!(
+                f,
+                "block {} ({}) is invalid: {}",
+                fault.height,
+                &fault.hash.to_string()[..16],
+                fault.reason
+            ),
+        }
+    }
+}
+
+/// Maximum number of orphan blocks held while waiting for their parents
+pub const MAX_ORPHAN_BLOCKS: usize = 100;
+
+/// Number of recent blocks whose transactions fee estimates are based on
+pub const FEE_ESTIMATE_BLOCKS: usize = 20;
+
 /// Simple blockchain structure to hold the chain state
-#[derive(Debug, Clone)]
+///
+/// With a store, block and account lookups and chain validation read from the
+/// store, and the in-memory chain, block tree and state act as its cache. A clone
+/// is detached from storage: it copies the chain in memory but has no store, so
+/// changes to the copy are never written anywhere.
+#[derive(Debug)]
 pub struct Blockchain {
+    /// Active chain, from the genesis block to the tip with the most cumulative work
     chain: VecDeque<Block>,
-    pending_transactions: Vec<Transaction>,
-    difficulty: u32,
+    /// Undo data of every block in `chain`, used to roll the state back on a reorganization
+    undo: VecDeque<StateUndo>,
+    /// Every known block, including side branches
+    tree: BlockTree,
+    /// Blocks whose parent is not known yet, oldest first
+    orphans: VecDeque<Block>,
+    /// Transactions waiting to be mined
+    mempool: Mempool,
+    /// Consensus rules of this chain
+    params: ConsensusParams,
+    /// Balances credited before the first block is applied
+    genesis_allocations: Vec<(Address, u64)>,
+    /// Account state after applying every block in `chain`
+    state: AccountState,
+    /// IDs of all transactions included in `chain`
+    txn_ids: HashSet<Hash32>,
+    /// Local time source for timestamp checks and mining
+    clock: Arc<dyn Clock>,
+    /// Hash of the genesis block, which every transaction must commit to
+    chain_id: Hash32,
+    /// Storage every connected block and state change is written to, and lookups read from
+    store: Option<Box<Mutex<dyn ChainStore>>>,
+    /// Active-chain heights changed since the state was last written to `store`
+    unsaved_heights: BTreeSet<u64>,
+    /// Accounts changed since the state was last written to `store`
+    unsaved_accounts: BTreeSet<Address>,
+}
+
+impl Clone for Blockchain {
+    fn clone(&self) -> Self {
+        Blockchain {
+            chain: self.chain.clone(),
+            undo: self.undo.clone(),
+            tree: self.tree.clone(),
+            orphans: self.orphans.clone(),
+            mempool: self.mempool.clone(),
+            params: self.params.clone(),
+            genesis_allocations: self.genesis_allocations.clone(),
+            state: self.state.clone(),
+            txn_ids: self.txn_ids.clone(),
+            clock: Arc::clone(&self.clock),
+            chain_id: self.chain_id,
+            store: None,
+            unsaved_heights: BTreeSet::new(),
+            unsaved_accounts: BTreeSet::new(),
+        }
+    }
 }
 
 impl Blockchain {
-    /// Create a new blockchain with genesis block
-    pub fn new(difficulty: u32) -> Self {
-        let mut blockchain = Blockchain {
-            chain: VecDeque::new(),
-            pending_transactions: Vec::new(),
-            difficulty,
-        };
+    /// Create a new private blockchain, mining its genesis block now
+    ///
+    /// `difficulty` is the expected number of hashes needed to mine a block. Every
+    /// call yields a different genesis block; nodes that need to share a chain
+    /// start from a [`GenesisConfig`] with [`Blockchain::with_genesis`].
+    pub fn new(difficulty: u64) -> Self {
+        Self::build(ConsensusParams::with_difficulty(difficulty), Vec::new(), AccountState::new(), Arc::new(SystemClock))
+    }
+
+    /// Create a new blockchain whose initial state credits the given balances
+    pub fn with_allocations(
+        difficulty: u64,
+        allocations: Vec<(Address, u64)>,
+    ) -> Result<Self, TransactionError> {
+        Self::with_params(ConsensusParams::with_difficulty(difficulty), allocations)
+    }
+
+    /// Create a new blockchain with explicit consensus parameters and initial balances
+    pub fn with_params(
+        params: ConsensusParams,
+        allocations: Vec<(Address, u64)>,
+    ) -> Result<Self, TransactionError> {
+        Self::with_clock(params, allocations, Arc::new(SystemClock))
+    }
+
+    /// Create a new blockchain that reads the current time from `clock`
+    ///
+    /// The genesis block is stamped with the clock's time.
+    pub fn with_clock(
+        params: ConsensusParams,
+        allocations: Vec<(Address, u64)>,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self, TransactionError> {
+        let state = AccountState::with_allocations(&allocations)?;
+        Ok(Self::build(params, allocations, state, clock))
+    }
+
+    /// Create the in-memory blockchain described by a genesis configuration
+    pub fn with_genesis(genesis: &GenesisConfig) -> Result<Self, GenesisError> {
+        Self::from_config(genesis, Arc::new(SystemClock))
+    }
+    
+    /// Create a new blockchain persisted in a [`FileStore`] in the directory `path`
+    ///
+    /// Use [`Blockchain::open`] to load the chain again.
+    pub fn create(path: impl AsRef<Path>, genesis: GenesisConfig) -> Result<Self, StoreError> {
+        genesis.validate()?;
+        Self::with_store(FileStore::create(path, genesis)?)
+    }
+    
+    /// Load a blockchain created with [`Blockchain::create`] from the directory `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::with_store(FileStore::open(path)?)
+    }
+    
+    /// Load the blockchain stored in the directory `path`, checking that it is the
+    /// chain described by `genesis`, or create it there if nothing is stored yet
+    pub fn open_or_create(path: impl AsRef<Path>, genesis: &GenesisConfig) -> Result<Self, StoreError> {
+        if !BlockStore::exists(&path) {
+            return Self::create(path, genesis.clone());
+        }
+        let blockchain = Self::open(path)?;
+        let expected = genesis.hash();
+        if blockchain.chain_id != expected {
+            return Err(GenesisError::Mismatch { expected, actual: blockchain.chain_id }.into());
+        }
+        Ok(blockchain)
+    }
+    
+    /// Create or load the blockchain held by `store`
+    ///
+    /// An empty store is given the genesis block of its [`GenesisConfig`].
+    /// Otherwise the stored block 0 must be that block; every stored block is
+    /// then added to the block tree, and the active chain and account state are
+    /// loaded as of the last state batch. Blocks stored after that batch, e.g.
+    /// right before a crash, are then connected if their branch has the most
+    /// work. Pending transactions are not stored.
+    ///
+    /// From then on every block is stored once it connects to the active chain, and
+    /// the resulting state is written as one batch per block or reorganization.
+    /// If a write fails, the error is returned and the unsaved changes are
+    /// written with the next batch.
+    pub fn with_store(mut store: impl ChainStore + 'static) -> Result<Self, StoreError> {
+        let genesis = store.genesis().clone();
+        let mut blockchain = Self::from_config(&genesis, Arc::new(SystemClock))?;
+        let blocks = store.blocks()?;
+        match blocks.first() {
+            Some(stored) => genesis.check_block(stored)?,
+            None => store.put_block(&blockchain.chain[0])?,
+        }
         
-        // Create genesis block
-        let mut genesis_block = Block::new(
-            0,
-            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
-            Vec::new(), // Genesis block has no transactions
-            difficulty,
-        );
+        for block in blocks.into_iter().skip(1) {
+            if blockchain.tree.contains(&block.header.parent_hash) {
+                blockchain.tree.insert(block.calculate_hash(), block);
+            }
+        }
+        match store.tip()? {
+            Some(_) => blockchain.load_active_chain(&store)?,
+            None => {
+                blockchain.unsaved_heights.insert(0);
+                blockchain.unsaved_accounts.extend(blockchain.state.iter().map(|(address, _)| *address));
+            }
+        }
+        blockchain.store = Some(Box::new(Mutex::new(store)));
+        
+        // Connect the branch with the most work; invalid blocks are dropped on the way
+        while let Some((best, work)) = blockchain.tree.most_work() {
+            if work <= blockchain.chain_work() {
+                break;
+            }
+            let _ = blockchain.reorganize(&best);
+        }
+        blockchain.save_state()?;
+        Ok(blockchain)
+    }
+    
+    /// Rebuild the active chain and the account state from `store`
+    ///
+    /// Active blocks at the top of the stored chain that are missing from the
+    /// block store, cut off as a torn final write, are rolled back with their
+    /// stored undo data; the new tip is written with the next batch.
+    fn load_active_chain(&mut self, store: &impl ChainStore) -> Result<(), StoreError> {
+        if store.hash_at_height(0)? != Some(self.chain_id) {
+            return Err(StoreError::MissingActiveBlock(0));
+        }
+        let mut lost = Vec::new();
+        let mut height = 1;
+        while let Some(hash) = store.hash_at_height(height)? {
+            let undo = store.undo_at_height(height)?.ok_or(StoreError::MissingActiveBlock(height))?;
+            match self.tree.get(&hash) {
+                Some(node) if lost.is_empty() => {
+                    let block = node.block.clone();
+                    self.txn_ids.extend(block.transactions.iter().map(|tx| tx.txn_id));
+                    self.chain.push_back(block);
+                    self.undo.push_back(undo);
+                }
+                _ => lost.push((height, undo)),
+            }
+            height += 1;
+        }
+        
+        self.state = AccountState::from_accounts(store.accounts()?);
+        for (height, undo) in lost.into_iter().rev() {
+            self.mark_unsaved(height, &undo);
+            self.state.revert_block(undo);
+        }
+        Ok(())
+    }
+    
+    /// Start the chain described by a genesis configuration
+    fn from_config(genesis: &GenesisConfig, clock: Arc<dyn Clock>) -> Result<Self, GenesisError> {
+        genesis.validate()?;
+        let allocations = genesis.allocation_list();
+        let state = AccountState::with_allocations(&allocations).map_err(GenesisError::InvalidAllocations)?;
+        Self::from_genesis(genesis.params.clone(), allocations, state, clock, genesis.block())
+            .map_err(GenesisError::InvalidBlock)
+    }
+    
+    fn build(
+        params: ConsensusParams,
+        genesis_allocations: Vec<(Address, u64)>,
+        state: AccountState,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let genesis_block = Self::mine_genesis(&params, &genesis_allocations, clock.as_ref());
+        Self::from_genesis(params, genesis_allocations, state, clock, genesis_block)
+            .expect("a freshly mined genesis block is valid")
+    }
+    
+    /// Mine a genesis block for `params` and `allocations` stamped with the clock's time
+    fn mine_genesis(params: &ConsensusParams, allocations: &[(Address, u64)], clock: &dyn Clock) -> Block {
+        // Genesis block has no transactions; its merkle root commits to the configuration
+        let mut genesis_block = genesis::genesis_block(params, allocations);
+        genesis_block.header.timestamp = clock.now();
         
         // Mine the genesis block to meet difficulty target
         genesis_block.mine_block();
+        genesis_block
+    }
+    
+    /// Start a chain from an existing genesis block
+    fn from_genesis(
+        params: ConsensusParams,
+        genesis_allocations: Vec<(Address, u64)>,
+        state: AccountState,
+        clock: Arc<dyn Clock>,
+        genesis_block: Block,
+    ) -> Result<Self, BlockError> {
+        let genesis_hash = genesis_block.calculate_hash();
+        let header = &genesis_block.header;
+        if header.block_height != 0 {
+            return Err(BlockError::InvalidHeight { expected: 0, actual: header.block_height });
+        }
+        if header.parent_hash != GENESIS_PARENT_HASH {
+            return Err(BlockError::ParentHashMismatch {
+                expected: GENESIS_PARENT_HASH,
+                actual: header.parent_hash,
+            });
+        }
+        if header.bits != params.initial_bits {
+            return Err(BlockError::UnexpectedBits { expected: params.initial_bits, actual: header.bits });
+        }
+        if !genesis_block.transactions.is_empty() {
+            return Err(BlockError::GenesisWithTransactions);
+        }
+        Self::check_genesis_commitment(&params, &genesis_allocations, &genesis_block)?;
+        genesis_block.check_structure()?;
+        if !header.meets_difficulty_target() {
+            return Err(BlockError::InsufficientProofOfWork(genesis_hash));
+        }
         
+        let mut blockchain = Blockchain {
+            chain: VecDeque::new(),
+            undo: VecDeque::new(),
+            tree: BlockTree::new(),
+            orphans: VecDeque::new(),
+            mempool: Mempool::default(),
+            params,
+            genesis_allocations,
+            state,
+            txn_ids: HashSet::new(),
+            clock,
+            chain_id: genesis_hash,
+            store: None,
+            unsaved_heights: BTreeSet::new(),
+            unsaved_accounts: BTreeSet::new(),
+        };
+        blockchain.tree.insert(genesis_hash, genesis_block.clone());
         blockchain.chain.push_back(genesis_block);
-        blockchain
+        blockchain.undo.push_back(StateUndo::default());
+        Ok(blockchain)
+    }
+    
+    /// Check that a genesis block's merkle root commits to `params` and `allocations`
+    fn check_genesis_commitment(
+        params: &ConsensusParams,
+        allocations: &[(Address, u64)],
+        genesis_block: &Block,
+    ) -> Result<(), BlockError> {
+        let expected = genesis::genesis_commitment(params, allocations);
+        if genesis_block.header.merkle_root != expected {
+            return Err(BlockError::MerkleRootMismatch {
+                expected,
+                actual: genesis_block.header.merkle_root,
+            });
+        }
+        Ok(())
     }
     
     /// Get the latest block in the chain
@@ -307,59 +923,642 @@ impl Blockchain {
     }
     
     /// Add a transaction to the pending pool
-    pub fn add_transaction(&mut self, transaction: Transaction) {
-        self.pending_transactions.push(transaction);
+    ///
+    /// The transaction is rejected unless its addresses parse, its `txn_id` matches
+    /// its contents, its signature verifies against the `from` public key and the
+    /// sender can afford `amount + fee` on top of its other pending transactions.
+    /// Its nonce must be the next one after the sender's confirmed and pending
+    /// transactions, or that of a pending transaction it replaces by paying a
+    /// higher fee (see [`Mempool::insert`]). Its `txn_id` must not already be in
+    /// the chain or the pool, and it must not have expired. A full pool only
+    /// admits it by evicting transactions with a lower fee rate.
+    pub fn add_transaction(&mut self, transaction: Transaction) -> Result<(), TransactionError> {
+        transaction.check_chain(&self.chain_id)?;
+        transaction.verify()?;
+
+        if self.txn_ids.contains(&transaction.txn_id) {
+            return Err(TransactionError::DuplicateTransaction(transaction.txn_id));
+        }
+        if self.mempool.contains(&transaction.txn_id) {
+            return Err(TransactionError::AlreadyPending(transaction.txn_id));
+        }
+
+        let now = self.clock.now();
+        self.mempool.expire(now);
+        let limit = self.mempool.expiry_limit(now);
+        if transaction.timestamp < limit {
+            return Err(TransactionError::Expired { timestamp: transaction.timestamp, limit });
+        }
+
+        let minimum = self.minimum_fee(transaction.size());
+        if transaction.fee < minimum {
+            return Err(TransactionError::FeeTooLow { fee: transaction.fee, minimum });
+        }
+
+        let sender = transaction.sender_address()?;
+        let replaced_spend = self.mempool
+            .get_by_nonce(&sender, transaction.nonce)
+            .map(|entry| entry.transaction.amount as u128 + entry.transaction.fee as u128);
+        if replaced_spend.is_none() {
+            let pending_from_sender = self.mempool.sender_count(&sender) as u64;
+            state::check_nonce(self.state.nonce_of(&sender) + pending_from_sender, transaction.nonce)?;
+        }
+
+        let required = transaction
+            .amount
+            .checked_add(transaction.fee)
+            .ok_or(TransactionError::AmountOverflow)?;
+        let pending_spend = self.mempool.sender_spend(&sender) - replaced_spend.unwrap_or(0);
+        let available = (self.state.balance_of(&sender) as u128).saturating_sub(pending_spend) as u64;
+        if available < required {
+            return Err(TransactionError::InsufficientFunds {
+                address: sender,
+                balance: available,
+                required,
+            });
+        }
+
+        self.mempool.insert(transaction, sender)?;
+        Ok(())
+    }
+    
+    /// Lowest fee the pending pool accepts for a transaction of `size` encoded
+    /// bytes: the higher of the consensus minimum and the pool's relay minimum
+    pub fn minimum_fee(&self, size: usize) -> u64 {
+        self.params.min_fee(size).max(self.mempool.config().min_relay_fee(size))
+    }
+    
+    /// Suggested fee per encoded byte: the median rate paid by the transactions
+    /// of the last [`FEE_ESTIMATE_BLOCKS`] active blocks, or the minimum rate if
+    /// they contain none
+    pub fn estimate_fee_rate(&self) -> u64 {
+        let minimum = self.params.min_fee_per_byte.max(self.mempool.config().min_relay_fee_per_byte);
+        let mut rates: Vec<u64> = self.chain
+            .iter()
+            .rev()
+            .take(FEE_ESTIMATE_BLOCKS)
+            .flat_map(|block| block.transactions.iter())
+            .filter(|tx| !tx.is_coinbase())
+            .map(|tx| tx.fee.div_ceil(tx.size() as u64))
+            .collect();
+        rates.sort_unstable();
+        rates.get(rates.len() / 2).map_or(minimum, |median| (*median).max(minimum))
+    }
+    
+    /// Suggested fee for a transaction of `size` encoded bytes, based on
+    /// [`Blockchain::estimate_fee_rate`] and never below [`Blockchain::minimum_fee`]
+    pub fn estimate_fee(&self, size: usize) -> u64 {
+        self.estimate_fee_rate()
+            .saturating_mul(size as u64)
+            .max(self.minimum_fee(size))
+    }
+    
+    /// The pool of pending transactions
+    pub fn mempool(&self) -> &Mempool {
+        &self.mempool
+    }
+    
+    /// Change the limits of the pending pool, evicting the lowest-fee
+    /// transactions if it no longer fits
+    pub fn set_mempool_config(&mut self, config: MempoolConfig) {
+        self.mempool.set_config(config);
+    }
+    
+    /// Builder for the next block on top of the tip, stamped with the local time
+    /// (nudged past the median-time-past if the clock lags)
+    pub fn block_template_builder(&self) -> BlockTemplateBuilder<'_> {
+        let median_time_past = self.chain_median_time_past(&self.chain, self.chain.len());
+        let earliest = median_time_past.map(|median| median + chrono::Duration::nanoseconds(1));
+        let timestamp = earliest.map_or(self.clock.now(), |earliest| earliest.max(self.clock.now()));
+        BlockTemplateBuilder::new(
+            &self.params,
+            &self.state,
+            self.chain_id,
+            self.chain.len() as u64,
+            self.tip_hash(),
+            self.next_bits(),
+            timestamp,
+        )
+    }
+    
+    /// Template for the next block paying `miner`, filled with the pending
+    /// transactions that pay the most and fit within the consensus limits
+    pub fn block_template(&self, miner: &Address) -> Result<BlockTemplate, BlockError> {
+        self.block_template_builder().build(miner, self.mempool.ordered())
     }
     
     /// Mine pending transactions into a new block
-    pub fn mine_pending_transactions(&mut self) -> Result<String, String> {
-        if self.pending_transactions.is_empty() {
-            return Err("No pending transactions to mine".to_string());
+    ///
+    /// The block is built by [`Blockchain::block_template`]: a coinbase paying the
+    /// scheduled subsidy for its height plus the collected fees to `miner`,
+    /// followed by the highest-paying pending transactions that fit. The rest
+    /// stay pending.
+    pub fn mine_pending_transactions(&mut self, miner: &Address) -> Result<Hash32, ChainError> {
+        let template = self.block_template(miner)?;
+        if template.transactions().is_empty() {
+            return Err(ChainError::NoPendingTransactions);
         }
+        let mut new_block = template.block;
+        let new_height = new_block.header.block_height;
         
-        let latest_block = self.get_latest_block()
-            .ok_or("No blocks in chain")?;
-        
-        let new_height = latest_block.header.block_height + 1;
-        let parent_hash = latest_block.calculate_hash();
-        
-        let mut new_block = Block::new(
-            new_height,
-            parent_hash,
-            self.pending_transactions.clone(),
-            self.difficulty,
-        );
+        // Apply to the account state first; a block that overdraws is never mined
+        let undo = Self::validate_block_transactions(&self.params, &self.chain_id, &new_block, &mut self.state, &mut self.txn_ids)?;
         
         let block_hash = new_block.mine_block();
         
+        if let Err(e) = self.persist(&new_block) {
+            self.state.revert_block(undo);
+            for tx in &new_block.transactions {
+                self.txn_ids.remove(&tx.txn_id);
+            }
+            return Err(e.into());
+        }
+        self.tree.insert(block_hash, new_block.clone());
+        self.mark_unsaved(new_height, &undo);
         self.chain.push_back(new_block);
-        self.pending_transactions.clear();
+        self.undo.push_back(undo);
+        self.prune_pending();
         
+        self.save_state()?;
         Ok(block_hash)
     }
     
+    /// Submit a block mined elsewhere, e.g. received from a peer or a pool
+    ///
+    /// The transaction count, merkle root and proof of work are checked first. A
+    /// block whose parent is unknown is held as an orphan and submitted again once
+    /// the parent is accepted. Otherwise the block must follow its parent in height
+    /// and time and meet the target the retarget rules require on its branch. It
+    /// becomes the new tip if it extends the active chain, triggers a
+    /// reorganization if its branch now has more cumulative work than the active
+    /// chain, and is otherwise kept as a side branch. Transactions are validated
+    /// when a block is connected; if that fails, the offending block and its
+    /// descendants are dropped and the previous active chain is left in place.
+    pub fn submit_block(&mut self, block: Block) -> Result<BlockStatus, BlockError> {
+        let hash = block.calculate_hash();
+        if self.tree.contains(&hash) || self.orphans.iter().any(|orphan| orphan.calculate_hash() == hash) {
+            return Err(BlockError::DuplicateBlock(hash));
+        }
+        block.check_structure()?;
+        consensus::validate_block_limits(&self.params, &block)?;
+        
+        if !self.tree.contains(&block.header.parent_hash) {
+            if self.orphans.len() >= MAX_ORPHAN_BLOCKS {
+                self.orphans.pop_front();
+            }
+            self.orphans.push_back(block);
+            return Ok(BlockStatus::Orphan);
+        }
+        
+        let status = self.accept_block(hash, block)?;
+        self.process_orphans(hash);
+        Ok(status)
+    }
+    
+    /// Submit again every orphan descending from the newly accepted block `hash`
+    ///
+    /// Orphans that turn out to be invalid are dropped.
+    fn process_orphans(&mut self, hash: Hash32) {
+        let mut accepted = vec![hash];
+        while let Some(parent_hash) = accepted.pop() {
+            let (children, rest): (VecDeque<Block>, VecDeque<Block>) = std::mem::take(&mut self.orphans)
+                .into_iter()
+                .partition(|orphan| orphan.header.parent_hash == parent_hash);
+            self.orphans = rest;
+            for child in children {
+                let child_hash = child.calculate_hash();
+                if self.accept_block(child_hash, child).is_ok() {
+                    accepted.push(child_hash);
+                }
+            }
+        }
+    }
+    
+    /// Number of orphan blocks waiting for their parent
+    pub fn orphan_count(&self) -> usize {
+        self.orphans.len()
+    }
+    
+    /// Check a structurally valid block against its known parent and add it to the
+    /// block tree, connecting it or reorganizing onto its branch if needed
+    fn accept_block(&mut self, hash: Hash32, block: Block) -> Result<BlockStatus, BlockError> {
+        if self.tree.contains(&hash) {
+            return Err(BlockError::DuplicateBlock(hash));
+        }
+        let parent = self.tree
+            .get(&block.header.parent_hash)
+            .ok_or(BlockError::UnknownParent(block.header.parent_hash))?;
+        let expected_height = parent.height() + 1;
+        if block.header.block_height != expected_height {
+            return Err(BlockError::InvalidHeight {
+                expected: expected_height,
+                actual: block.header.block_height,
+            });
+        }
+        let expected_bits = self.bits_after(&block.header.parent_hash);
+        if block.header.bits != expected_bits {
+            return Err(BlockError::UnexpectedBits {
+                expected: expected_bits,
+                actual: block.header.bits,
+            });
+        }
+        let median_time_past = self.branch_median_time_past(&block.header.parent_hash)
+            .unwrap_or(parent.block.header.timestamp);
+        consensus::validate_timestamp(&self.params, block.header.timestamp, median_time_past, self.clock.now())?;
+        if !block.header.meets_difficulty_target() {
+            return Err(BlockError::InsufficientProofOfWork(hash));
+        }
+        
+        let extends_tip = block.header.parent_hash == self.tip_hash();
+        let chain_work = self.tree.insert(hash, block.clone());
+        
+        let status = if extends_tip {
+            if let Err(e) = self.connect_block(block) {
+                self.tree.remove_with_descendants(&hash);
+                return Err(e);
+            }
+            self.prune_pending();
+            BlockStatus::ExtendedTip
+        } else if chain_work > self.chain_work() {
+            self.reorganize(&hash)?
+        } else {
+            BlockStatus::SideBranch
+        };
+        
+        self.save_state()?;
+        Ok(status)
+    }
+    
+    /// Switch the active chain to the branch ending at `new_tip`
+    ///
+    /// Blocks above the fork point are disconnected and their state changes rolled
+    /// back, then the new branch is connected block by block. Transactions of the
+    /// disconnected blocks that the new branch does not include go back to the
+    /// pending pool.
+    fn reorganize(&mut self, new_tip: &Hash32) -> Result<BlockStatus, BlockError> {
+        // Blocks of the new branch that are not on the active chain, lowest first
+        let branch = self.tree.branch_until(new_tip, |hash, node| self.is_active(hash, node.height()));
+        let fork_height = match branch.first().and_then(|hash| self.tree.get(hash)) {
+            Some(node) => node.height() - 1,
+            None => return Ok(BlockStatus::SideBranch),
+        };
+        
+        let mut disconnected = Vec::new();
+        while self.chain.len() as u64 > fork_height + 1 {
+            match self.disconnect_tip() {
+                Some(block) => disconnected.push(block),
+                None => break,
+            }
+        }
+        
+        for (connected, hash) in branch.iter().enumerate() {
+            let block = self.tree.get(hash).expect("branch blocks are in the tree").block.clone();
+            if let Err(e) = self.connect_block(block) {
+                // Drop the invalid block with everything built on it and restore the previous chain
+                self.tree.remove_with_descendants(hash);
+                for _ in 0..connected {
+                    self.disconnect_tip();
+                }
+                for block in disconnected.into_iter().rev() {
+                    self.connect_block(block).expect("previously connected blocks reconnect");
+                }
+                return Err(e);
+            }
+        }
+        
+        let status = BlockStatus::Reorganized {
+            disconnected: disconnected.len(),
+            connected: branch.len(),
+        };
+        let returned = disconnected
+            .into_iter()
+            .rev()
+            .flat_map(|block| block.transactions)
+            .filter(|tx| !tx.is_coinbase())
+            .collect();
+        self.refresh_pending(returned);
+        Ok(status)
+    }
+    
+    /// Write a block to the store, if the chain has one
+    fn persist(&self, block: &Block) -> Result<(), StoreError> {
+        match &self.store {
+            Some(store) => store.lock().unwrap_or_else(PoisonError::into_inner).put_block(block),
+            None => Ok(()),
+        }
+    }
+    
+    /// Record that the active-chain block at `height` and the accounts it touched changed
+    fn mark_unsaved(&mut self, height: u64, undo: &StateUndo) {
+        self.unsaved_heights.insert(height);
+        self.unsaved_accounts.extend(undo.addresses());
+    }
+    
+    /// Write the tip and every unsaved active-chain entry and account to the store
+    /// as one batch
+    fn save_state(&mut self) -> Result<(), StoreError> {
+        let store = match &self.store {
+            Some(store) => store,
+            None => {
+                self.unsaved_heights.clear();
+                self.unsaved_accounts.clear();
+                return Ok(());
+            }
+        };
+        let batch = StateBatch {
+            tip: Some(self.tip_hash()),
+            active: self.unsaved_heights
+                .iter()
+                .map(|&height| {
+                    let entry = self.chain
+                        .get(height as usize)
+                        .map(|block| (block.calculate_hash(), self.undo[height as usize].clone()));
+                    (height, entry)
+                })
+                .collect(),
+            accounts: self.unsaved_accounts
+                .iter()
+                .map(|address| (*address, self.state.get(address)))
+                .collect(),
+        };
+        store.lock().unwrap_or_else(PoisonError::into_inner).write_batch(batch)?;
+        self.unsaved_heights.clear();
+        self.unsaved_accounts.clear();
+        Ok(())
+    }
+    
+    /// Validate a block's transactions against the tip state, write the block to
+    /// the store and append it to the active chain
+    ///
+    /// Blocks are only stored once their transactions are valid, so side branches
+    /// that never connected are not kept across restarts.
+    fn connect_block(&mut self, block: Block) -> Result<(), BlockError> {
+        let undo = Self::validate_block_transactions(&self.params, &self.chain_id, &block, &mut self.state, &mut self.txn_ids)?;
+        if let Err(e) = self.persist(&block) {
+            self.state.revert_block(undo);
+            for tx in &block.transactions {
+                self.txn_ids.remove(&tx.txn_id);
+            }
+            return Err(e.into());
+        }
+        self.mark_unsaved(block.header.block_height, &undo);
+        self.chain.push_back(block);
+        self.undo.push_back(undo);
+        Ok(())
+    }
+    
+    /// Remove the tip of the active chain and roll back its state changes
+    ///
+    /// The genesis block is never disconnected.
+    fn disconnect_tip(&mut self) -> Option<Block> {
+        if self.chain.len() <= 1 {
+            return None;
+        }
+        let block = self.chain.pop_back()?;
+        if let Some(undo) = self.undo.pop_back() {
+            self.mark_unsaved(block.header.block_height, &undo);
+            self.state.revert_block(undo);
+        }
+        for tx in &block.transactions {
+            self.txn_ids.remove(&tx.txn_id);
+        }
+        Some(block)
+    }
+    
+    /// Drop the transactions of the new tip block from the pending pool, along
+    /// with those it invalidated and those that expired
+    fn prune_pending(&mut self) {
+        if let Some(tip) = self.chain.back() {
+            self.mempool.remove_confirmed(tip, &self.state);
+        }
+        self.mempool.expire(self.clock.now());
+    }
+    
+    /// Rebuild the pending pool after a reorganization
+    ///
+    /// Transactions returned from disconnected blocks are offered first, followed by
+    /// the previous pool. Anything already confirmed or no longer valid on the new
+    /// tip is dropped.
+    fn refresh_pending(&mut self, returned: Vec<Transaction>) {
+        let previous = self.mempool.drain();
+        for tx in returned.into_iter().chain(previous) {
+            // Rejected transactions are either confirmed or conflict with the new chain
+            let _ = self.add_transaction(tx);
+        }
+    }
+    
+    /// Whether the block `hash` at `height` is part of the active chain
+    fn is_active(&self, hash: &Hash32, height: u64) -> bool {
+        self.chain
+            .get(height as usize)
+            .is_some_and(|block| block.calculate_hash() == *hash)
+    }
+    
     /// Get blockchain statistics
-    pub fn get_stats(&self) -> (usize, usize, u32) {
-        (self.chain.len(), self.pending_transactions.len(), self.difficulty)
+    pub fn get_stats(&self) -> (usize, usize, f64) {
+        (self.chain.len(), self.mempool.len(), self.get_difficulty())
     }
     
     /// Validate the entire blockchain
     pub fn is_chain_valid(&self) -> bool {
-        for i in 1..self.chain.len() {
-            let current_block = &self.chain[i];
-            let previous_block = &self.chain[i - 1];
+        self.validate_chain().is_valid()
+    }
+    
+    /// Validate every block of the active chain from the genesis block up and
+    /// report the first invalid one
+    ///
+    /// Each block must sit at the height matching its position, link to the hash of
+    /// the block below it (the genesis block to [`GENESIS_PARENT_HASH`]), use the
+    /// target dictated by the retarget rules and meet it, and match its declared
+    /// transaction count and merkle root. Replaying the transactions from the
+    /// genesis allocations must never overdraw an account or use an out-of-order
+    /// nonce.
+    ///
+    /// With a store, the blocks checked are the ones it holds for the active chain,
+    /// so a stored block that was lost or altered is reported as invalid.
+    pub fn validate_chain(&self) -> ChainReport {
+        let fault = |height: usize, block: &Block, reason: BlockError| ChainReport {
+            blocks_checked: height,
+            first_invalid: Some(ChainFault {
+                height: height as u64,
+                hash: block.calculate_hash(),
+                reason,
+            }),
+        };
+        
+        let chain = match self.active_chain() {
+            Ok(chain) => chain,
+            Err((height, e)) => return fault(height, &self.chain[height], e.into()),
+        };
+        let mut state = match AccountState::with_allocations(&self.genesis_allocations) {
+            Ok(state) => state,
+            Err(e) => match chain.front() {
+                Some(genesis) => return fault(0, genesis, e.into()),
+                None => return ChainReport { blocks_checked: 0, first_invalid: None },
+            },
+        };
+        let mut seen = HashSet::new();
+        
+        for i in 0..chain.len() {
+            if let Err(reason) = self.validate_chain_block(&chain, i, &mut state, &mut seen) {
+                return fault(i, &chain[i], reason);
+            }
+        }
+        ChainReport {
+            blocks_checked: chain.len(),
+            first_invalid: None,
+        }
+    }
+    
+    /// The active chain as held by the store, with heights not yet written taken
+    /// from memory; without a store, the in-memory chain
+    ///
+    /// A failed read is returned with the height it failed at.
+    fn active_chain(&self) -> Result<Cow<'_, VecDeque<Block>>, (usize, StoreError)> {
+        let store = match &self.store {
+            Some(store) => store.lock().unwrap_or_else(PoisonError::into_inner),
+            None => return Ok(Cow::Borrowed(&self.chain)),
+        };
+        let mut chain = VecDeque::with_capacity(self.chain.len());
+        for (height, cached) in self.chain.iter().enumerate() {
+            if self.unsaved_heights.contains(&(height as u64)) {
+                chain.push_back(cached.clone());
+                continue;
+            }
+            match store.block_at_height(height as u64) {
+                Ok(Some(block)) => chain.push_back(block),
+                Ok(None) => return Err((height, StoreError::MissingActiveBlock(height as u64))),
+                Err(e) => return Err((height, e)),
+            }
+        }
+        Ok(Cow::Owned(chain))
+    }
+    
+    /// Check the block at position `index` of `chain` and apply it to `state`
+    fn validate_chain_block(
+        &self,
+        chain: &VecDeque<Block>,
+        index: usize,
+        state: &mut AccountState,
+        seen: &mut HashSet<Hash32>,
+    ) -> Result<(), BlockError> {
+        let block = &chain[index];
+        let header = &block.header;
+        if header.block_height != index as u64 {
+            return Err(BlockError::InvalidHeight {
+                expected: index as u64,
+                actual: header.block_height,
+            });
+        }
+        
+        let expected_parent = match index {
+            0 => GENESIS_PARENT_HASH,
+            _ => chain[index - 1].calculate_hash(),
+        };
+        if header.parent_hash != expected_parent {
+            return Err(BlockError::ParentHashMismatch {
+                expected: expected_parent,
+                actual: header.parent_hash,
+            });
+        }
+        if index == 0 {
+            if !block.transactions.is_empty() {
+                return Err(BlockError::GenesisWithTransactions);
+            }
+            Self::check_genesis_commitment(&self.params, &self.genesis_allocations, block)?;
+        }
+        
+        // Every block must use the target dictated by the retarget rules
+        let expected_bits = self.expected_bits(chain, index);
+        if header.bits != expected_bits {
+            return Err(BlockError::UnexpectedBits {
+                expected: expected_bits,
+                actual: header.bits,
+            });
+        }
+        
+        // The local clock is not consulted: drift is only checked when a block arrives
+        if let Some(median_time_past) = self.chain_median_time_past(chain, index) {
+            if header.timestamp <= median_time_past {
+                return Err(BlockError::TimestampNotAfterMedian {
+                    timestamp: header.timestamp,
+                    median_time_past,
+                });
+            }
+        }
+        
+        block.check_structure()?;
+        consensus::validate_block_limits(&self.params, block)?;
+        Self::validate_block_transactions(&self.params, &self.chain_id, block, state, seen)?;
+        Ok(())
+    }
+    
+    /// Median timestamp of the blocks below `height` on the active chain that the
+    /// block at `height` must exceed (`None` for the genesis block)
+    fn chain_median_time_past(&self, chain: &VecDeque<Block>, height: usize) -> Option<DateTime<Utc>> {
+        let span = self.params.median_time_span.max(1) as usize;
+        let start = height.saturating_sub(span);
+        let end = height.min(chain.len());
+        consensus::median_time(chain.range(start..end).map(|block| block.header.timestamp).collect())
+    }
+    
+    /// Median timestamp of the last blocks of the branch ending at `tip_hash`
+    fn branch_median_time_past(&self, tip_hash: &Hash32) -> Option<DateTime<Utc>> {
+        let span = self.params.median_time_span.max(1) as usize;
+        let mut timestamps = Vec::with_capacity(span);
+        let mut current = self.tree.get(tip_hash);
+        while let Some(node) = current {
+            timestamps.push(node.block.header.timestamp);
+            if timestamps.len() == span || node.height() == 0 {
+                break;
+            }
+            current = self.tree.get(node.parent_hash());
+        }
+        consensus::median_time(timestamps)
+    }
+    
+    /// Median-time-past of the active chain: the next block's timestamp must be later
+    pub fn median_time_past(&self) -> Option<DateTime<Utc>> {
+        self.chain_median_time_past(&self.chain, self.chain.len())
+    }
+    
+    /// Check the coinbase, signatures and uniqueness of a block's transactions and
+    /// apply them to `state`
+    ///
+    /// `seen` holds the IDs of the transactions already in the chain; the block's
+    /// IDs are added to it only if the whole block is valid.
+    fn validate_block_transactions(
+        params: &ConsensusParams,
+        chain_id: &Hash32,
+        block: &Block,
+        state: &mut AccountState,
+        seen: &mut HashSet<Hash32>,
+    ) -> Result<StateUndo, BlockError> {
+        if block.header.block_height > 0 {
+            let subsidy = params.monetary.block_subsidy(block.header.block_height);
+            consensus::validate_coinbase(block, subsidy)?;
+        }
+        
+        let mut block_ids = HashSet::new();
+        for (index, tx) in block.transactions.iter().enumerate() {
+            tx.check_chain(chain_id)?;
             
-            // Check if current block's parent hash matches previous block's hash
-            if current_block.header.parent_hash != previous_block.calculate_hash() {
-                return false;
+            // Every transaction other than the coinbase must carry a valid signature
+            // and pay at least the minimum fee
+            if !(index == 0 && tx.is_coinbase()) {
+                tx.verify()?;
+                consensus::validate_fee(params, tx)?;
             }
             
-            // Check if current block meets difficulty target
-            if !current_block.header.meets_difficulty_target() {
-                return false;
+            // A transaction may appear at most once in the whole chain
+            if seen.contains(&tx.txn_id) || !block_ids.insert(tx.txn_id) {
+                return Err(TransactionError::DuplicateTransaction(tx.txn_id).into());
             }
         }
-        true
+        
+        let undo = state.apply_block(block)?;
+        seen.extend(block_ids);
+        Ok(undo)
     }
     
     /// Get the chain length
@@ -369,12 +1568,120 @@ impl Blockchain {
     
     /// Get pending transactions count
     pub fn pending_count(&self) -> usize {
-        self.pending_transactions.len()
+        self.mempool.len()
+    }
+    
+    /// Get mining difficulty (the difficulty required of the next block)
+    pub fn get_difficulty(&self) -> f64 {
+        difficulty::difficulty_from_bits(self.next_bits())
+    }
+    
+    /// Compact target the next block on top of the chain must use
+    pub fn next_bits(&self) -> u32 {
+        self.expected_bits(&self.chain, self.chain.len())
+    }
+    
+    /// Compact target required of a block built on `parent_hash`, computed from
+    /// that block's branch
+    fn bits_after(&self, parent_hash: &Hash32) -> u32 {
+        let parent = match self.tree.get(parent_hash) {
+            Some(parent) => parent,
+            None => return self.params.initial_bits,
+        };
+        let start_height = difficulty::window_start_height(&self.params, parent.height() + 1);
+        let window_start = self.tree
+            .ancestor(parent_hash, start_height)
+            .and_then(|hash| self.tree.get(&hash))
+            .unwrap_or(parent);
+        difficulty::next_bits(&self.params, &parent.block.header, &window_start.block.header)
+    }
+    
+    /// ID of this chain: the hash of its genesis block
+    ///
+    /// Transactions commit to it in their signing message and are only accepted
+    /// on the chain they name.
+    pub fn chain_id(&self) -> Hash32 {
+        self.chain_id
+    }
+    
+    /// Hash of the tip of the active chain
+    pub fn tip_hash(&self) -> Hash32 {
+        self.get_latest_block().map_or(Hash32::ZERO, Block::calculate_hash)
+    }
+    
+    /// Cumulative work of the active chain
+    pub fn chain_work(&self) -> U256 {
+        self.tree.get(&self.tip_hash()).map_or(U256::ZERO, |node| node.chain_work)
+    }
+    
+    /// Look up any known block, on the active chain or a side branch, by hash
+    pub fn block_by_hash(&self, hash: &Hash32) -> Option<&Block> {
+        self.tree.get(hash).map(|node| &node.block)
+    }
+    
+    /// Look up a block by hash: in the block tree, which caches every known block,
+    /// and otherwise in the store
+    pub fn get_block(&self, hash: &Hash32) -> Result<Option<Block>, StoreError> {
+        if let Some(block) = self.block_by_hash(hash) {
+            return Ok(Some(block.clone()));
+        }
+        match &self.store {
+            Some(store) => store.lock().unwrap_or_else(PoisonError::into_inner).block(hash),
+            None => Ok(None),
+        }
+    }
+    
+    /// Compact target required of the block at `height`, computed from the blocks below it
+    fn expected_bits(&self, chain: &VecDeque<Block>, height: usize) -> u32 {
+        if height == 0 {
+            return self.params.initial_bits;
+        }
+        let parent = &chain[height - 1].header;
+        let window_start = difficulty::window_start_height(&self.params, height as u64);
+        difficulty::next_bits(&self.params, parent, &chain[window_start as usize].header)
+    }
+    
+    /// Total units in existence: genesis allocations plus every subsidy issued up to the tip
+    pub fn circulating_supply(&self) -> u64 {
+        let allocated = self.genesis_allocations
+            .iter()
+            .fold(0u64, |total, (_, amount)| total.saturating_add(*amount));
+        let next_height = self.get_latest_block().map_or(0, |block| block.header.block_height + 1);
+        allocated.saturating_add(self.params.monetary.issued_before(next_height))
+    }
+    
+    /// Consensus parameters of this chain
+    pub fn params(&self) -> &ConsensusParams {
+        &self.params
+    }
+    
+    /// Confirmed balance of an address
+    pub fn balance_of(&self, address: &Address) -> u64 {
+        self.account(address).balance
+    }
+    
+    /// Next nonce expected from an address (number of its confirmed transactions)
+    pub fn nonce_of(&self, address: &Address) -> u64 {
+        self.account(address).nonce
+    }
+    
+    /// Confirmed account of an address, read from the store unless it has changes
+    /// not yet written there; the in-memory state answers those, and stands in
+    /// when there is no store or it cannot be read
+    fn account(&self, address: &Address) -> Account {
+        if let Some(store) = &self.store {
+            if !self.unsaved_accounts.contains(address) {
+                if let Ok(account) = store.lock().unwrap_or_else(PoisonError::into_inner).account(address) {
+                    return account.unwrap_or_default();
+                }
+            }
+        }
+        self.state.get(address).unwrap_or_default()
     }
     
-    /// Get mining difficulty
-    pub fn get_difficulty(&self) -> u32 {
-        self.difficulty
+    /// Account state at the tip of the chain
+    pub fn state(&self) -> &AccountState {
+        &self.state
     }
     
     /// Display a block's full details in a separate terminal window
@@ -389,7 +1696,8 @@ impl Blockchain {
         full_block_display.push_str(&format!("   Parent Hash: {}\n", block.header.parent_hash));
         full_block_display.push_str(&format!("   Merkle Root: {}\n", block.header.merkle_root));
         full_block_display.push_str(&format!("   Timestamp: {}\n", block.header.timestamp));
-        full_block_display.push_str(&format!("   Difficulty: {}\n", block.header.difficulty));
+        full_block_display.push_str(&format!("   Bits: {:#010x}\n", block.header.bits));
+        full_block_display.push_str(&format!("   Difficulty: {:.2}\n", difficulty::difficulty_from_bits(block.header.bits)));
         full_block_display.push_str(&format!("   Nonce: {}\n", block.header.nonce));
         full_block_display.push_str(&format!("   Block Hash: {}\n", block.calculate_hash()));
         
@@ -399,15 +1707,17 @@ impl Blockchain {
         } else {
             full_block_display.push_str("\nTRANSACTION DETAILS:\n");
             for (i, tx) in block.transactions.iter().enumerate() {
-                full_block_display.push_str(&format!("   \n   Transaction #{}\n", i + 1));
+                let kind = if tx.is_coinbase() { " (coinbase)" } else { "" };
+                full_block_display.push_str(&format!("   \n   Transaction #{}{}\n", i + 1, kind));
                 full_block_display.push_str(&format!("   - ID: {}\n", tx.txn_id));
                 full_block_display.push_str(&format!("   - From: {}\n", tx.from));
                 full_block_display.push_str(&format!("   - To: {}\n", tx.to));
-                full_block_display.push_str(&format!("   - Amount: {} units ({:.3} I tokens)\n", tx.amount, tx.amount as f64 / 1_000_000_000.0));
-                full_block_display.push_str(&format!("   - Fee: {} units ({:.3} I tokens)\n", tx.fee, tx.fee as f64 / 1_000_000_000.0));
+                full_block_display.push_str(&format!("   - Amount: {} units ({:.3} I tokens)\n", tx.amount, tx.amount as f64 / COIN as f64));
+                full_block_display.push_str(&format!("   - Fee: {} units ({:.3} I tokens)\n", tx.fee, tx.fee as f64 / COIN as f64));
                 full_block_display.push_str(&format!("   - Nonce: {}\n", tx.nonce));
                 full_block_display.push_str(&format!("   - Timestamp: {}\n", tx.timestamp));
-                full_block_display.push_str(&format!("   - Signature: {}\n", tx.signature));
+                let signature = tx.signature.map_or_else(|| "(none)".to_string(), |signature| signature.to_string());
+                full_block_display.push_str(&format!("   - Signature: {}\n", signature));
                 full_block_display.push_str(&format!("   - Hash: {}\n", tx.calculate_hash()));
             }
         }
@@ -416,8 +1726,8 @@ impl Blockchain {
         full_block_display.push_str(&format!("   Total Transactions: {}\n", block.transactions.len()));
         let total_amount: u64 = block.transactions.iter().map(|tx| tx.amount).sum();
         let total_fees: u64 = block.transactions.iter().map(|tx| tx.fee).sum();
-        full_block_display.push_str(&format!("   Total Amount Transferred: {} units ({:.3} I tokens)\n", total_amount, total_amount as f64 / 1_000_000_000.0));
-        full_block_display.push_str(&format!("   Total Fees Collected: {} units ({:.3} I tokens)\n", total_fees, total_fees as f64 / 1_000_000_000.0));
+        full_block_display.push_str(&format!("   Total Amount Transferred: {} units ({:.3} I tokens)\n", total_amount, total_amount as f64 / COIN as f64));
+        full_block_display.push_str(&format!("   Total Fees Collected: {} units ({:.3} I tokens)\n", total_fees, total_fees as f64 / COIN as f64));
         full_block_display.push_str(&format!("   Block Size (JSON): {} bytes\n", serde_json::to_string(block).unwrap_or_default().len()));
         
         full_block_display.push_str("\nSERIALIZED BLOCK (JSON):\n");
@@ -1,4 +1,10 @@
-use i_protocol::{Blockchain, TRANSACTION_FEE};
+use i_protocol::{BlockStore, Blockchain, GenesisConfig, COIN, TRANSACTION_FEE};
+
+/// Directory the chain is stored in unless another one is given on the command line
+const DEFAULT_DATA_DIR: &str = "i-protocol-data";
+
+/// Network joined unless another one, or a genesis file, is given on the command line
+const DEFAULT_NETWORK: &str = "mainnet";
 
 fn main() {
     println!("=== I Protocol Blockchain Node ===");
@@ -7,8 +13,29 @@ fn main() {
     println!("Language: Rust");
     println!("Philosophy: Practical, Performance-focused, Light but Powerful\n");
     
-    // Initialize blockchain with difficulty 4
-    let blockchain = Blockchain::new(4);
+    // Load the stored chain, or start the network named (or genesis file given)
+    // on the command line
+    let mut args = std::env::args().skip(1);
+    let data_dir = args.next().unwrap_or_else(|| DEFAULT_DATA_DIR.to_string());
+    let network = args.next().unwrap_or_else(|| DEFAULT_NETWORK.to_string());
+    let genesis = match GenesisConfig::named(&network) {
+        Some(genesis) => genesis,
+        None => match GenesisConfig::load(&network) {
+            Ok(genesis) => genesis,
+            Err(e) => {
+                eprintln!("[ERROR] Cannot load genesis configuration {}: {}", network, e);
+                std::process::exit(1);
+            }
+        },
+    };
+    let created = !BlockStore::exists(&data_dir);
+    let blockchain = match Blockchain::open_or_create(&data_dir, &genesis) {
+        Ok(blockchain) => blockchain,
+        Err(e) => {
+            eprintln!("[ERROR] Cannot load {} chain from {}: {}", genesis.chain_name, data_dir, e);
+            std::process::exit(1);
+        }
+    };
     
     // Display genesis block in separate terminal
     if let Some(genesis) = blockchain.get_latest_block() {
@@ -16,15 +43,21 @@ fn main() {
     }
     
     println!("[INIT] I Protocol blockchain initialized");
-    println!("   Genesis block created");
-    println!("   Difficulty: {} (require {} leading zeros)", blockchain.get_difficulty(), blockchain.get_difficulty());
-    println!("   Transaction fee: {} units (0.001 I tokens)", TRANSACTION_FEE);
+    println!("   Network: {} (chain ID {})", genesis.chain_name, blockchain.chain_id());
+    if created {
+        println!("   Genesis block created in {}", data_dir);
+    } else {
+        println!("   Chain loaded from {}", data_dir);
+    }
+    println!("   Difficulty: {:.2} (bits {:#010x})", blockchain.get_difficulty(), blockchain.next_bits());
+    println!("   Default transaction fee: {} units ({} I tokens)", TRANSACTION_FEE, TRANSACTION_FEE as f64 / COIN as f64);
+    println!("   Suggested fee rate: {} units per byte", blockchain.estimate_fee_rate());
     
     println!("\n[STATUS] Blockchain Status:");
     println!("   Chain length: {} blocks", blockchain.chain_length());
     println!("   Pending transactions: {}", blockchain.pending_count());
-    println!("   Mining difficulty: {}", blockchain.get_difficulty());
-    println!("   Chain valid: {}", blockchain.is_chain_valid());
+    println!("   Mining difficulty: {:.2}", blockchain.get_difficulty());
+    println!("   Chain valid: {}", blockchain.validate_chain());
     
     if let Some(latest_block) = blockchain.get_latest_block() {
         println!("   Latest block hash: {}", latest_block.calculate_hash());
@@ -33,12 +66,9 @@ fn main() {
     
     println!("\n[READY] I Protocol blockchain node is ready for transactions");
     println!("\nNext steps:");
-    println!("   1. Implement transaction creation API");
-    println!("   2. Add Ed25519 signature verification");
-    println!("   3. Implement network layer for peer communication");
-    println!("   4. Add wallet functionality");
-    println!("   5. Create REST API endpoints");
+    println!("   1. Implement network layer for peer communication");
+    println!("   2. Create REST API endpoints");
     
-    println!("\n[INFO] To add transactions and mine blocks, use the blockchain API (to be implemented)");
-    println!("[INFO] Current state: Clean blockchain with genesis block only");
+    println!("\n[INFO] Sign transactions with a Wallet, then add and mine them through the Blockchain API");
+    println!("[INFO] Chain data is stored in {}", data_dir);
 }
\ No newline at end of file
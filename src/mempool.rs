@@ -0,0 +1,458 @@
+//! Pool of pending transactions waiting to be mined
+//!
+//! Entries are indexed by transaction ID and by sender and nonce, and ranked by
+//! fee per byte of their wire encoding, then by absolute fee. When the pool is
+//! full, a new transaction may only enter by evicting entries ranked below it;
+//! an evicted entry takes the same sender's later nonces with it, since they can
+//! no longer be mined. Entries whose `timestamp` is older than the configured
+//! expiry are dropped the same way.
+//!
+//! A sender can bump the fee of a pending transaction by sending another one
+//! with the same nonce (replace-by-fee). The replacement must raise the fee by
+//! the configured percentage, and each nonce can only be replaced a limited
+//! number of times, so that a sender cannot make the pool churn through a
+//! long series of replacements for a negligible total increase.
+//!
+//! The pool does not check signatures, nonces, balances or minimum fees itself;
+//! the [`Blockchain`](crate::Blockchain) validates transactions before inserting
+//! them. The relay minimum fee in [`MempoolConfig`] lets a node turn away
+//! transactions that pay the consensus minimum but less than it is willing to
+//! relay.
+
+use crate::address::Address;
+use crate::consensus;
+use crate::error::TransactionError;
+use crate::primitives::Hash32;
+use crate::state::AccountState;
+use crate::{Block, Transaction};
+use chrono::{DateTime, Duration, Utc};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::ops::Bound;
+
+/// Default limit on the number of pending transactions
+pub const DEFAULT_MAX_MEMPOOL_TRANSACTIONS: usize = 50_000;
+/// Default limit on the total encoded size of pending transactions, in bytes
+pub const DEFAULT_MAX_MEMPOOL_BYTES: usize = 32 * 1024 * 1024;
+/// Default age after which a pending transaction is dropped, in seconds (two weeks)
+pub const DEFAULT_MEMPOOL_EXPIRY_SECS: u64 = 14 * 24 * 60 * 60;
+/// Default minimum fee of a transaction admitted to the pool
+pub const DEFAULT_MIN_RELAY_FEE: u64 = 10_000;
+/// Default minimum fee per encoded byte of a transaction admitted to the pool
+pub const DEFAULT_MIN_RELAY_FEE_PER_BYTE: u64 = 50;
+/// Default minimum fee increase of a replacement transaction, in percent
+pub const DEFAULT_MIN_REPLACEMENT_INCREMENT_PERCENT: u64 = 10;
+/// Default number of times a pending transaction may be replaced
+pub const DEFAULT_MAX_REPLACEMENTS: u32 = 10;
+
+/// Node-local limits of the pending pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MempoolConfig {
+    /// Maximum number of pending transactions
+    pub max_transactions: usize,
+    /// Maximum total encoded size of pending transactions, in bytes
+    pub max_bytes: usize,
+    /// Age, measured from `Transaction::timestamp`, after which a transaction is dropped
+    pub expiry_secs: u64,
+    /// Minimum fee of an admitted transaction
+    pub min_relay_fee: u64,
+    /// Minimum fee per encoded byte of an admitted transaction
+    pub min_relay_fee_per_byte: u64,
+    /// Percentage by which a replacement must raise the fee of the transaction it replaces
+    pub min_replacement_increment_percent: u64,
+    /// Number of times the pending transaction of a sender and nonce may be replaced
+    pub max_replacements: u32,
+}
+
+impl MempoolConfig {
+    /// Minimum fee an admitted transaction of `size` encoded bytes must pay
+    pub fn min_relay_fee(&self, size: usize) -> u64 {
+        consensus::fee_floor(self.min_relay_fee, self.min_relay_fee_per_byte, size)
+    }
+
+    /// Minimum fee of a transaction replacing a pending one that pays `fee`:
+    /// at least one unit more, and at least the configured percentage more
+    pub fn min_replacement_fee(&self, fee: u64) -> u64 {
+        let increment = (fee as u128 * self.min_replacement_increment_percent as u128).div_ceil(100);
+        let required = fee as u128 + increment.max(1);
+        u64::try_from(required).unwrap_or(u64::MAX)
+    }
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        MempoolConfig {
+            max_transactions: DEFAULT_MAX_MEMPOOL_TRANSACTIONS,
+            max_bytes: DEFAULT_MAX_MEMPOOL_BYTES,
+            expiry_secs: DEFAULT_MEMPOOL_EXPIRY_SECS,
+            min_relay_fee: DEFAULT_MIN_RELAY_FEE,
+            min_relay_fee_per_byte: DEFAULT_MIN_RELAY_FEE_PER_BYTE,
+            min_replacement_increment_percent: DEFAULT_MIN_REPLACEMENT_INCREMENT_PERCENT,
+            max_replacements: DEFAULT_MAX_REPLACEMENTS,
+        }
+    }
+}
+
+/// A pending transaction together with the data the pool indexes it by
+#[derive(Debug, Clone)]
+pub struct MempoolEntry {
+    /// The pending transaction
+    pub transaction: Transaction,
+    /// Address of the sender
+    pub sender: Address,
+    /// Length of the transaction's wire encoding in bytes
+    pub size: usize,
+    /// Number of transactions this one replaced in turn
+    pub replacements: u32,
+}
+
+impl MempoolEntry {
+    fn priority(&self) -> Priority {
+        Priority {
+            fee: self.transaction.fee,
+            size: self.size,
+            txn_id: self.transaction.txn_id,
+        }
+    }
+}
+
+/// Rank of an entry: fee per byte, then absolute fee, with the transaction ID
+/// breaking ties so that the order is total
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Priority {
+    fee: u64,
+    size: usize,
+    txn_id: Hash32,
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let rate = self.fee as u128 * other.size as u128;
+        let other_rate = other.fee as u128 * self.size as u128;
+        rate.cmp(&other_rate)
+            .then(self.fee.cmp(&other.fee))
+            .then_with(|| self.txn_id.as_bytes().cmp(other.txn_id.as_bytes()))
+    }
+}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Fee-prioritized pool of pending transactions
+#[derive(Debug, Clone, Default)]
+pub struct Mempool {
+    config: MempoolConfig,
+    entries: HashMap<Hash32, MempoolEntry>,
+    /// Pending transaction IDs of every sender, by nonce
+    by_sender: HashMap<Address, BTreeMap<u64, Hash32>>,
+    /// Every entry, lowest priority first
+    by_priority: BTreeSet<Priority>,
+    total_bytes: usize,
+}
+
+impl Mempool {
+    /// Create an empty pool with the given limits
+    pub fn new(config: MempoolConfig) -> Self {
+        Mempool {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Limits of this pool
+    pub fn config(&self) -> &MempoolConfig {
+        &self.config
+    }
+
+    /// Change the limits, evicting the lowest-priority entries until the pool fits
+    pub fn set_config(&mut self, config: MempoolConfig) {
+        self.config = config;
+        while self.entries.len() > self.config.max_transactions || self.total_bytes > self.config.max_bytes {
+            match self.by_priority.first() {
+                Some(lowest) => {
+                    let txn_id = lowest.txn_id;
+                    self.remove_with_descendants(&txn_id);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Number of pending transactions
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the pool is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total encoded size of the pending transactions, in bytes
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    /// Whether the transaction `txn_id` is pending
+    pub fn contains(&self, txn_id: &Hash32) -> bool {
+        self.entries.contains_key(txn_id)
+    }
+
+    /// Pending entry with the ID `txn_id`
+    pub fn get(&self, txn_id: &Hash32) -> Option<&MempoolEntry> {
+        self.entries.get(txn_id)
+    }
+
+    /// Pending transaction sent by `sender` with the given nonce
+    pub fn get_by_nonce(&self, sender: &Address, nonce: u64) -> Option<&MempoolEntry> {
+        let txn_id = self.by_sender.get(sender)?.get(&nonce)?;
+        self.entries.get(txn_id)
+    }
+
+    /// Pending transactions of `sender`, in nonce order
+    pub fn sender_transactions<'a>(&'a self, sender: &Address) -> impl Iterator<Item = &'a Transaction> + 'a {
+        self.by_sender
+            .get(sender)
+            .into_iter()
+            .flat_map(|nonces| nonces.values())
+            .map(|txn_id| &self.entries[txn_id].transaction)
+    }
+
+    /// Number of pending transactions sent by `sender`
+    pub fn sender_count(&self, sender: &Address) -> usize {
+        self.by_sender.get(sender).map_or(0, BTreeMap::len)
+    }
+
+    /// Total amount and fees of the pending transactions sent by `sender`
+    pub fn sender_spend(&self, sender: &Address) -> u128 {
+        self.sender_transactions(sender)
+            .map(|tx| tx.amount as u128 + tx.fee as u128)
+            .sum()
+    }
+
+    /// Pending transactions in the order they should be mined: highest priority
+    /// first, with every sender's transactions kept in nonce order
+    pub fn ordered(&self) -> Vec<&Transaction> {
+        let mut heads: BinaryHeap<Priority> = self.by_sender
+            .values()
+            .filter_map(|nonces| nonces.values().next())
+            .map(|txn_id| self.entries[txn_id].priority())
+            .collect();
+        let mut ordered = Vec::with_capacity(self.entries.len());
+        while let Some(head) = heads.pop() {
+            let entry = &self.entries[&head.txn_id];
+            ordered.push(&entry.transaction);
+            let next = self.by_sender[&entry.sender]
+                .range((Bound::Excluded(entry.transaction.nonce), Bound::Unbounded))
+                .next();
+            if let Some((_, txn_id)) = next {
+                heads.push(self.entries[txn_id].priority());
+            }
+        }
+        ordered
+    }
+
+    /// Add a validated transaction from `sender`, evicting lower-priority entries
+    /// of other senders if the pool is full
+    ///
+    /// If the sender already has a pending transaction with the same nonce, the
+    /// new one replaces it and the replaced transaction is returned. Fails if the
+    /// transaction is already pending, does not meet the replacement rules or
+    /// cannot be made to fit.
+    pub fn insert(&mut self, transaction: Transaction, sender: Address) -> Result<Option<Transaction>, TransactionError> {
+        if self.entries.contains_key(&transaction.txn_id) {
+            return Err(TransactionError::AlreadyPending(transaction.txn_id));
+        }
+        let mut entry = MempoolEntry {
+            size: transaction.size(),
+            transaction,
+            sender,
+            replacements: 0,
+        };
+
+        let replaced = match self.get_by_nonce(&sender, entry.transaction.nonce) {
+            Some(pending) => {
+                let required = self.config.min_replacement_fee(pending.transaction.fee);
+                if entry.transaction.fee < required {
+                    return Err(TransactionError::ReplacementFeeTooLow { fee: entry.transaction.fee, required });
+                }
+                if pending.replacements >= self.config.max_replacements {
+                    return Err(TransactionError::TooManyReplacements { limit: self.config.max_replacements });
+                }
+                entry.replacements = pending.replacements + 1;
+                let txn_id = pending.transaction.txn_id;
+                self.remove_entry(&txn_id)
+            }
+            None => None,
+        };
+
+        let evicted = match self.eviction_candidates(&entry) {
+            Ok(evicted) => evicted,
+            Err(e) => {
+                if let Some(replaced) = replaced {
+                    self.insert_entry(replaced);
+                }
+                return Err(e);
+            }
+        };
+        for txn_id in &evicted {
+            self.remove(txn_id);
+        }
+        self.insert_entry(entry);
+        Ok(replaced.map(|replaced| replaced.transaction))
+    }
+
+    fn insert_entry(&mut self, entry: MempoolEntry) {
+        let sender = entry.sender;
+        self.total_bytes += entry.size;
+        self.by_priority.insert(entry.priority());
+        self.by_sender
+            .entry(sender)
+            .or_default()
+            .insert(entry.transaction.nonce, entry.transaction.txn_id);
+        self.entries.insert(entry.transaction.txn_id, entry);
+    }
+
+    /// Entries to evict so that `entry` fits, lowest priority first
+    ///
+    /// Only entries ranked below `entry` and sent by someone else are evicted,
+    /// each together with its sender's later nonces.
+    fn eviction_candidates(&self, entry: &MempoolEntry) -> Result<Vec<Hash32>, TransactionError> {
+        let fits = |count: usize, bytes: usize| {
+            count < self.config.max_transactions && bytes + entry.size <= self.config.max_bytes
+        };
+        let mut count = self.entries.len();
+        let mut bytes = self.total_bytes;
+        let mut evicted = Vec::new();
+        let mut marked = HashSet::new();
+        let priority = entry.priority();
+        for lowest in &self.by_priority {
+            if fits(count, bytes) {
+                break;
+            }
+            if *lowest >= priority {
+                return Err(TransactionError::MempoolFull);
+            }
+            let victim = &self.entries[&lowest.txn_id];
+            if victim.sender == entry.sender || marked.contains(&lowest.txn_id) {
+                continue;
+            }
+            for txn_id in self.by_sender[&victim.sender].range(victim.transaction.nonce..).map(|(_, id)| id) {
+                if marked.insert(*txn_id) {
+                    count -= 1;
+                    bytes -= self.entries[txn_id].size;
+                    evicted.push(*txn_id);
+                }
+            }
+        }
+        if !fits(count, bytes) {
+            return Err(TransactionError::MempoolFull);
+        }
+        Ok(evicted)
+    }
+
+    /// Remove a single entry
+    pub fn remove(&mut self, txn_id: &Hash32) -> Option<Transaction> {
+        self.remove_entry(txn_id).map(|entry| entry.transaction)
+    }
+
+    fn remove_entry(&mut self, txn_id: &Hash32) -> Option<MempoolEntry> {
+        let entry = self.entries.remove(txn_id)?;
+        self.total_bytes -= entry.size;
+        self.by_priority.remove(&entry.priority());
+        if let Some(nonces) = self.by_sender.get_mut(&entry.sender) {
+            nonces.remove(&entry.transaction.nonce);
+            if nonces.is_empty() {
+                self.by_sender.remove(&entry.sender);
+            }
+        }
+        Some(entry)
+    }
+
+    /// Remove an entry together with every later nonce of the same sender
+    pub fn remove_with_descendants(&mut self, txn_id: &Hash32) -> Vec<Transaction> {
+        let Some(entry) = self.entries.get(txn_id) else {
+            return Vec::new();
+        };
+        let ids: Vec<Hash32> = self.by_sender[&entry.sender]
+            .range(entry.transaction.nonce..)
+            .map(|(_, id)| *id)
+            .collect();
+        ids.iter().filter_map(|id| self.remove(id)).collect()
+    }
+
+    /// Remove the transactions of a newly connected block, then drop every
+    /// pending transaction of the block's senders that no longer follows their
+    /// confirmed nonce or that they can no longer afford in `state`
+    pub fn remove_confirmed(&mut self, block: &Block, state: &AccountState) {
+        let mut senders = BTreeSet::new();
+        for tx in block.transactions.iter().filter(|tx| !tx.is_coinbase()) {
+            self.remove(&tx.txn_id);
+            if let Ok(sender) = tx.sender_address() {
+                senders.insert(sender);
+            }
+        }
+        for sender in &senders {
+            self.revalidate_sender(sender, state);
+        }
+    }
+
+    fn revalidate_sender(&mut self, sender: &Address, state: &AccountState) {
+        let Some(nonces) = self.by_sender.get(sender) else {
+            return;
+        };
+        let account = state.account(sender);
+        let mut expected = account.nonce;
+        let mut available = account.balance as u128;
+        let mut invalid = Vec::new();
+        for (nonce, txn_id) in nonces {
+            let tx = &self.entries[txn_id].transaction;
+            let required = tx.amount as u128 + tx.fee as u128;
+            if *nonce < account.nonce {
+                // Confirmed, or replaced by a conflicting transaction
+                invalid.push(*txn_id);
+            } else if *nonce != expected || required > available {
+                invalid.extend(nonces.range(nonce..).map(|(_, id)| *id));
+                break;
+            } else {
+                expected += 1;
+                available -= required;
+            }
+        }
+        for txn_id in &invalid {
+            self.remove(txn_id);
+        }
+    }
+
+    /// Earliest transaction timestamp the pool accepts at time `now`
+    pub fn expiry_limit(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        i64::try_from(self.config.expiry_secs)
+            .ok()
+            .and_then(Duration::try_seconds)
+            .and_then(|age| now.checked_sub_signed(age))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+
+    /// Drop every transaction older than the expiry limit at time `now`, with the
+    /// later nonces of its sender
+    pub fn expire(&mut self, now: DateTime<Utc>) -> Vec<Transaction> {
+        let limit = self.expiry_limit(now);
+        let expired: Vec<Hash32> = self.entries
+            .values()
+            .filter(|entry| entry.transaction.timestamp < limit)
+            .map(|entry| entry.transaction.txn_id)
+            .collect();
+        expired.iter().flat_map(|txn_id| self.remove_with_descendants(txn_id)).collect()
+    }
+
+    /// Remove every entry, returning them in the order of [`Mempool::ordered`]
+    pub fn drain(&mut self) -> Vec<Transaction> {
+        let ordered = self.ordered().into_iter().cloned().collect();
+        let config = self.config.clone();
+        *self = Self::new(config);
+        ordered
+    }
+}
@@ -0,0 +1,131 @@
+//! Binary Blake3 Merkle tree over transaction IDs
+//!
+//! Leaves and internal nodes are hashed with distinct prefixes so an internal
+//! node can never be passed off as a leaf (and vice versa):
+//!
+//! ```text
+//! leaf = Blake3(0x00 || txn_id)
+//! node = Blake3(0x01 || left || right)
+//! ```
+//!
+//! When a level has an odd number of nodes the last one is promoted to the next
+//! level unchanged rather than being paired with a copy of itself.
+
+use crate::primitives::Hash32;
+use serde::{Deserialize, Serialize};
+
+/// Merkle root of a block without transactions
+pub const EMPTY_MERKLE_ROOT: Hash32 = Hash32::ZERO;
+
+/// Domain separation prefix for leaf hashes
+const LEAF_PREFIX: u8 = 0x00;
+/// Domain separation prefix for internal node hashes
+const NODE_PREFIX: u8 = 0x01;
+
+/// Which side of the path a sibling hash sits on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// One level of an inclusion proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofStep {
+    /// Sibling hash
+    pub hash: Hash32,
+    /// Side of the sibling relative to the running hash
+    pub side: Side,
+}
+
+/// Inclusion proof of a transaction in a block's Merkle tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// Position of the transaction in the block
+    pub index: usize,
+    /// Sibling hashes from the leaf up to the root
+    pub steps: Vec<ProofStep>,
+}
+
+/// Hash of a leaf holding `txn_id`
+pub fn leaf_hash(txn_id: &Hash32) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(txn_id.as_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+/// Hash of an internal node
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    *hasher.finalize().as_bytes()
+}
+
+/// Hash every pair of a level; an odd trailing node is promoted unchanged
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right),
+            [single] => *single,
+            _ => unreachable!("chunks(2) yields one or two nodes"),
+        })
+        .collect()
+}
+
+/// Compute the Merkle root of a list of transaction IDs
+pub fn merkle_root(txn_ids: &[Hash32]) -> Hash32 {
+    if txn_ids.is_empty() {
+        return EMPTY_MERKLE_ROOT;
+    }
+
+    let mut level: Vec<[u8; 32]> = txn_ids.iter().map(leaf_hash).collect();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    Hash32::from_bytes(level[0])
+}
+
+/// Build the inclusion proof for the transaction at `index`
+pub fn build_proof(txn_ids: &[Hash32], index: usize) -> Option<MerkleProof> {
+    if index >= txn_ids.len() {
+        return None;
+    }
+
+    let mut steps = Vec::new();
+    let mut level: Vec<[u8; 32]> = txn_ids.iter().map(leaf_hash).collect();
+    let mut position = index;
+
+    while level.len() > 1 {
+        let sibling = position ^ 1;
+        if sibling < level.len() {
+            let side = if sibling < position { Side::Left } else { Side::Right };
+            steps.push(ProofStep {
+                hash: Hash32::from_bytes(level[sibling]),
+                side,
+            });
+        }
+        level = next_level(&level);
+        position /= 2;
+    }
+
+    Some(MerkleProof { index, steps })
+}
+
+/// Check that `txn_id` is included under the Merkle `root`
+pub fn verify_merkle_proof(root: &Hash32, txn_id: &Hash32, proof: &MerkleProof) -> bool {
+    let mut current = leaf_hash(txn_id);
+
+    for step in &proof.steps {
+        let sibling = step.hash.as_bytes();
+        current = match step.side {
+            Side::Left => node_hash(sibling, &current),
+            Side::Right => node_hash(&current, sibling),
+        };
+    }
+
+    Hash32::from_bytes(current) == *root
+}
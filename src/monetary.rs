@@ -0,0 +1,98 @@
+//! Monetary policy of the I token
+//!
+//! The block subsidy starts at `initial_subsidy` and halves every
+//! `halving_interval` blocks. Issuance stops once `max_supply` units have been
+//! created; the block that reaches the cap receives only the remainder.
+
+use crate::COIN;
+use serde::{Deserialize, Serialize};
+
+/// Default subsidy of the first era (50 I)
+pub const DEFAULT_INITIAL_SUBSIDY: u64 = 50 * COIN;
+/// Default number of blocks between two halvings
+pub const DEFAULT_HALVING_INTERVAL: u64 = 210_000;
+/// Default hard cap on the amount of I ever issued through block subsidies (21M I)
+pub const DEFAULT_MAX_SUPPLY: u64 = 21_000_000 * COIN;
+
+/// Emission schedule of newly issued units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonetaryPolicy {
+    /// Subsidy paid by blocks of the first era
+    pub initial_subsidy: u64,
+    /// Number of blocks per era; the subsidy halves at every era boundary
+    pub halving_interval: u64,
+    /// Maximum total subsidy ever issued
+    pub max_supply: u64,
+}
+
+impl Default for MonetaryPolicy {
+    fn default() -> Self {
+        MonetaryPolicy {
+            initial_subsidy: DEFAULT_INITIAL_SUBSIDY,
+            halving_interval: DEFAULT_HALVING_INTERVAL,
+            max_supply: DEFAULT_MAX_SUPPLY,
+        }
+    }
+}
+
+impl MonetaryPolicy {
+    /// A policy paying the same subsidy forever, bounded only by `u64::MAX`
+    pub fn fixed(subsidy: u64) -> Self {
+        MonetaryPolicy {
+            initial_subsidy: subsidy,
+            halving_interval: u64::MAX,
+            max_supply: u64::MAX,
+        }
+    }
+
+    /// Subsidy before the supply cap is applied
+    fn scheduled_subsidy(&self, block_height: u64) -> u64 {
+        if self.halving_interval == 0 {
+            return 0;
+        }
+        let halvings = block_height / self.halving_interval;
+        if halvings >= 64 {
+            0
+        } else {
+            self.initial_subsidy >> halvings
+        }
+    }
+
+    /// Total subsidy issued by all blocks strictly below `block_height`
+    ///
+    /// The genesis block (height 0) issues nothing.
+    pub fn issued_before(&self, block_height: u64) -> u64 {
+        if block_height <= 1 || self.halving_interval == 0 {
+            return 0;
+        }
+
+        // Heights 1..block_height, summed era by era
+        let mut total: u128 = 0;
+        let mut start = 1u64;
+        while start < block_height {
+            let subsidy = self.scheduled_subsidy(start);
+            if subsidy == 0 {
+                break;
+            }
+            let era_end = (start / self.halving_interval)
+                .saturating_add(1)
+                .saturating_mul(self.halving_interval);
+            let end = era_end.min(block_height);
+            total += subsidy as u128 * (end - start) as u128;
+            if total >= self.max_supply as u128 {
+                return self.max_supply;
+            }
+            start = end;
+        }
+        total as u64
+    }
+
+    /// Subsidy of the block at `block_height`, taking the supply cap into account
+    pub fn block_subsidy(&self, block_height: u64) -> u64 {
+        if block_height == 0 {
+            return 0;
+        }
+        let remaining = self.max_supply - self.issued_before(block_height);
+        self.scheduled_subsidy(block_height).min(remaining)
+    }
+}
@@ -0,0 +1,177 @@
+//! Fixed-size byte types for hashes, public keys and signatures
+//!
+//! Each type wraps a byte array of exactly the right length, so a value that
+//! exists is always well-formed. They are written as lowercase hex in JSON and
+//! `Display`, and parsing or deserializing anything else fails immediately.
+//! Equality runs in constant time so comparisons against secret-dependent
+//! values do not leak where the first differing byte is.
+
+use ed25519_dalek::VerifyingKey;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+/// Reasons a hex string does not parse into a fixed-size value
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum HexError {
+    #[error("invalid hex: {0}")]
+    InvalidHex(String),
+    #[error("expected {expected} bytes, found {actual}")]
+    InvalidLength { expected: usize, actual: usize },
+}
+
+/// Reasons a public key cannot be used to verify signatures
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum KeyError {
+    #[error("not a valid Ed25519 public key: {0}")]
+    InvalidPublicKey(String),
+}
+
+/// Decode a hex string into exactly `N` bytes
+fn decode_hex<const N: usize>(value: &str) -> Result<[u8; N], HexError> {
+    let bytes = hex::decode(value).map_err(|e| HexError::InvalidHex(e.to_string()))?;
+    let actual = bytes.len();
+    bytes
+        .try_into()
+        .map_err(|_| HexError::InvalidLength { expected: N, actual })
+}
+
+macro_rules! fixed_bytes {
+    ($(#[$meta:meta])* $name:ident, $len:expr) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy)]
+        pub struct $name([u8; $len]);
+
+        impl $name {
+            /// Length in bytes
+            pub const LEN: usize = $len;
+
+            /// Wrap raw bytes
+            pub const fn from_bytes(bytes: [u8; $len]) -> Self {
+                $name(bytes)
+            }
+
+            /// Raw bytes
+            pub fn as_bytes(&self) -> &[u8; $len] {
+                &self.0
+            }
+
+            /// Parse a hex string of exactly the right length
+            pub fn from_hex(value: &str) -> Result<Self, HexError> {
+                decode_hex(value).map($name)
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.0.ct_eq(&other.0).into()
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.hash(state);
+            }
+        }
+
+        impl From<[u8; $len]> for $name {
+            fn from(bytes: [u8; $len]) -> Self {
+                $name(bytes)
+            }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", hex::encode(self.0))
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = HexError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::from_hex(s)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let s = String::deserialize(deserializer)?;
+                s.parse().map_err(de::Error::custom)
+            }
+        }
+    };
+}
+
+fixed_bytes!(
+    /// A 32-byte Blake3 hash (transaction IDs, block hashes, Merkle roots)
+    Hash32,
+    32
+);
+
+fixed_bytes!(
+    /// A 32-byte Ed25519 public key
+    ///
+    /// Any 32 bytes are accepted; whether they form a valid curve point is only
+    /// checked by [`PublicKey::verifying_key`].
+    PublicKey,
+    32
+);
+
+fixed_bytes!(
+    /// A 64-byte Ed25519 signature
+    Sig64,
+    64
+);
+
+impl Hash32 {
+    /// The all-zero hash
+    pub const ZERO: Hash32 = Hash32([0; 32]);
+
+    /// Blake3 hash of `data`
+    pub fn digest(data: &[u8]) -> Self {
+        Hash32(*blake3::hash(data).as_bytes())
+    }
+}
+
+impl PublicKey {
+    /// Decode the key as an Ed25519 verifying key
+    pub fn verifying_key(&self) -> Result<VerifyingKey, KeyError> {
+        VerifyingKey::from_bytes(&self.0).map_err(|e| KeyError::InvalidPublicKey(e.to_string()))
+    }
+}
+
+impl From<&VerifyingKey> for PublicKey {
+    fn from(key: &VerifyingKey) -> Self {
+        PublicKey(key.to_bytes())
+    }
+}
+
+impl From<ed25519_dalek::Signature> for Sig64 {
+    fn from(signature: ed25519_dalek::Signature) -> Self {
+        Sig64(signature.to_bytes())
+    }
+}
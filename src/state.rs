@@ -0,0 +1,208 @@
+//! Account state (world state) derived from the chain
+//!
+//! The state maps every address to its balance and nonce. It is built by
+//! crediting the genesis allocations and then applying every block in order.
+
+use crate::address::Address;
+use crate::error::TransactionError;
+use crate::{Block, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Balance and nonce of a single account
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Account {
+    /// Spendable balance in smallest units
+    pub balance: u64,
+    /// Number of transactions sent from this account
+    pub nonce: u64,
+}
+
+/// Accounts touched by a block, as they were before it was applied
+///
+/// Returned by [`AccountState::apply_block`] and consumed by
+/// [`AccountState::revert_block`] to roll the block back during a reorganization.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateUndo {
+    previous: BTreeMap<Address, Option<Account>>,
+}
+
+impl StateUndo {
+    /// Addresses of the accounts the block touched
+    pub fn addresses(&self) -> impl Iterator<Item = &Address> {
+        self.previous.keys()
+    }
+}
+
+/// Mapping from addresses to accounts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountState {
+    accounts: BTreeMap<Address, Account>,
+}
+
+impl AccountState {
+    /// Create an empty state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a state seeded with initial balances
+    pub fn with_allocations(allocations: &[(Address, u64)]) -> Result<Self, TransactionError> {
+        let mut state = Self::new();
+        for (address, amount) in allocations {
+            state.credit(address, *amount)?;
+        }
+        Ok(state)
+    }
+
+    /// Create a state holding exactly the given accounts
+    pub fn from_accounts(accounts: impl IntoIterator<Item = (Address, Account)>) -> Self {
+        AccountState {
+            accounts: accounts.into_iter().collect(),
+        }
+    }
+
+    /// Account for an address, if it has ever been touched
+    pub fn get(&self, address: &Address) -> Option<Account> {
+        self.accounts.get(address).copied()
+    }
+
+    /// All touched accounts, ordered by address
+    pub fn iter(&self) -> impl Iterator<Item = (&Address, &Account)> {
+        self.accounts.iter()
+    }
+
+    /// Account for an address (empty if it has never been seen)
+    pub fn account(&self, address: &Address) -> Account {
+        self.accounts.get(address).copied().unwrap_or_default()
+    }
+
+    /// Balance of an address
+    pub fn balance_of(&self, address: &Address) -> u64 {
+        self.account(address).balance
+    }
+
+    /// Nonce of an address
+    pub fn nonce_of(&self, address: &Address) -> u64 {
+        self.account(address).nonce
+    }
+
+    /// Sum of all balances
+    pub fn total_balance(&self) -> u128 {
+        self.accounts.values().map(|account| account.balance as u128).sum()
+    }
+
+    /// Number of accounts with a recorded balance or nonce
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Whether no account has been touched yet
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Add `amount` to the balance of `address`
+    pub fn credit(&mut self, address: &Address, amount: u64) -> Result<(), TransactionError> {
+        let account = self.accounts.entry(*address).or_default();
+        account.balance = account
+            .balance
+            .checked_add(amount)
+            .ok_or(TransactionError::AmountOverflow)?;
+        Ok(())
+    }
+
+    /// Apply a transfer: debit `amount + fee` and bump the nonce of the sender,
+    /// credit `amount` to the recipient
+    ///
+    /// The transaction nonce must equal the sender's current nonce. The state is
+    /// left untouched if the transaction is rejected.
+    pub fn apply_transaction(&mut self, transaction: &Transaction) -> Result<(), TransactionError> {
+        if transaction.is_coinbase() {
+            return Err(TransactionError::MisplacedCoinbase);
+        }
+        let sender = transaction.sender_address()?;
+        let recipient = transaction.to;
+        let required = transaction
+            .amount
+            .checked_add(transaction.fee)
+            .ok_or(TransactionError::AmountOverflow)?;
+
+        let sender_account = self.account(&sender);
+        check_nonce(sender_account.nonce, transaction.nonce)?;
+        if sender_account.balance < required {
+            return Err(TransactionError::InsufficientFunds {
+                address: sender,
+                balance: sender_account.balance,
+                required,
+            });
+        }
+        if sender != recipient {
+            self.account(&recipient)
+                .balance
+                .checked_add(transaction.amount)
+                .ok_or(TransactionError::AmountOverflow)?;
+        }
+
+        let account = self.accounts.entry(sender).or_default();
+        account.balance -= required;
+        account.nonce += 1;
+        self.credit(&recipient, transaction.amount)
+    }
+
+    /// Apply every transaction of a block in order
+    ///
+    /// A coinbase in first position credits the miner. Either the whole block is
+    /// applied and its undo data returned or, on the first rejected transaction,
+    /// the state is restored and the error returned.
+    pub fn apply_block(&mut self, block: &Block) -> Result<StateUndo, TransactionError> {
+        let mut touched: BTreeMap<Address, Option<Account>> = BTreeMap::new();
+
+        for (index, transaction) in block.transactions.iter().enumerate() {
+            for address in transaction.sender_address().into_iter().chain([transaction.to]) {
+                touched
+                    .entry(address)
+                    .or_insert_with(|| self.accounts.get(&address).copied());
+            }
+
+            let result = if index == 0 && transaction.is_coinbase() {
+                self.credit(&transaction.to, transaction.amount)
+            } else {
+                self.apply_transaction(transaction)
+            };
+            if let Err(e) = result {
+                self.restore(touched);
+                return Err(e);
+            }
+        }
+        Ok(StateUndo { previous: touched })
+    }
+
+    /// Roll back a block applied by [`AccountState::apply_block`]
+    ///
+    /// Blocks must be reverted in the reverse order they were applied.
+    pub fn revert_block(&mut self, undo: StateUndo) {
+        self.restore(undo.previous);
+    }
+
+    /// Put back previously saved accounts
+    fn restore(&mut self, saved: BTreeMap<Address, Option<Account>>) {
+        for (address, account) in saved {
+            match account {
+                Some(account) => self.accounts.insert(address, account),
+                None => self.accounts.remove(&address),
+            };
+        }
+    }
+}
+
+/// Check a transaction nonce against the next nonce expected from its sender
+pub fn check_nonce(expected: u64, actual: u64) -> Result<(), TransactionError> {
+    if actual < expected {
+        Err(TransactionError::NonceTooLow { expected, actual })
+    } else if actual > expected {
+        Err(TransactionError::NonceGap { expected, actual })
+    } else {
+        Ok(())
+    }
+}
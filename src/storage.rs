@@ -0,0 +1,730 @@
+//! Chain storage backends
+//!
+//! A [`ChainStore`] holds every block that has been connected to the active
+//! chain together with the chain state: the tip, the active chain by height with
+//! the undo data of each of its blocks, and the accounts. State changes are
+//! written in [`StateBatch`]es, each applied entirely or not at all, so a store
+//! never holds a half-applied block or reorganization. [`MemoryStore`] keeps
+//! everything in memory; [`FileStore`] keeps it in a directory.
+//!
+//! A file store directory holds the chain's [`GenesisConfig`] (`genesis.json`),
+//! numbered segment files (`blk00000.dat`, ...) holding every stored block in
+//! the order it was first stored, and a state log (`state.log`) of state
+//! batches. Each block or batch is written as one record: a magic number, the
+//! length of the payload, the Blake3 checksum of the payload and the payload
+//! itself, which is the block's canonical encoding or the batch as JSON. A new
+//! segment is started once the current one would grow past its size limit.
+//!
+//! Records are only ever appended and each append is flushed to disk before it
+//! returns, so a crash can at worst leave a torn record at the end of the last
+//! segment or of the state log: one that is cut short, or that runs to the end
+//! of the file but fails its checksum. Opening the store cuts such a record off;
+//! a damaged record anywhere else is reported as corruption. The block
+//! index by hash and height is rebuilt from the records on open, and the state
+//! log is replayed and, once replayed in full, rewritten as a single snapshot.
+
+use crate::address::Address;
+use crate::encoding::{Decoder, Encoder};
+use crate::genesis::{GenesisConfig, GenesisError};
+use crate::primitives::Hash32;
+use crate::state::{Account, StateUndo};
+use crate::Block;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Magic number at the start of every block record
+pub const RECORD_MAGIC: [u8; 4] = *b"IBLK";
+
+/// Magic number at the start of every state log record
+pub const STATE_RECORD_MAGIC: [u8; 4] = *b"ISTA";
+
+/// Size of a record header: magic, payload length and payload checksum
+pub const RECORD_HEADER_LEN: usize = 4 + 4 + 32;
+
+/// Default size a segment file may reach before a new one is started
+pub const DEFAULT_MAX_SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+
+/// File name of the stored genesis configuration
+const GENESIS_FILE: &str = "genesis.json";
+
+/// File name of the state log
+const STATE_LOG_FILE: &str = "state.log";
+
+/// Errors produced by chain stores
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("block store I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("block store format error: {0}")]
+    Format(#[from] serde_json::Error),
+    #[error("a block store already exists at {0}")]
+    AlreadyExists(PathBuf),
+    #[error("segment {0} is missing")]
+    MissingSegment(u32),
+    #[error("corrupt record in segment {segment} at offset {offset}")]
+    CorruptRecord { segment: u32, offset: u64 },
+    #[error("corrupt record in the state log at offset {offset}")]
+    CorruptStateRecord { offset: u64 },
+    #[error("invalid stored genesis: {0}")]
+    Genesis(#[from] GenesisError),
+    #[error("stored active chain has no block at height {0}")]
+    MissingActiveBlock(u64),
+}
+
+/// Persistence for the blocks and state of one chain
+///
+/// A store holds the chain of a [`Blockchain`](crate::Blockchain): it is written
+/// as blocks connect, read back when the chain is loaded, and answers block and
+/// account lookups, for which the chain's in-memory blocks and state act as a
+/// cache. Reads of state reflect every batch written so far. Implementations must
+/// apply each [`StateBatch`] atomically.
+pub trait ChainStore: fmt::Debug + Send {
+    /// Genesis configuration of the stored chain
+    fn genesis(&self) -> &GenesisConfig;
+
+    /// Look up a stored block by hash, on the active chain or a side branch
+    fn block(&self, hash: &Hash32) -> Result<Option<Block>, StoreError>;
+
+    /// Store a block; storing a block twice has no effect
+    fn put_block(&mut self, block: &Block) -> Result<(), StoreError>;
+
+    /// Every stored block in the order it was first stored
+    fn blocks(&self) -> Result<Vec<Block>, StoreError>;
+
+    /// Hash of the tip of the active chain, or `None` before the first batch
+    fn tip(&self) -> Result<Option<Hash32>, StoreError>;
+
+    /// Hash of the active-chain block at `height`
+    fn hash_at_height(&self, height: u64) -> Result<Option<Hash32>, StoreError>;
+
+    /// Undo data of the active-chain block at `height`
+    fn undo_at_height(&self, height: u64) -> Result<Option<StateUndo>, StoreError>;
+
+    /// Account stored for `address`
+    fn account(&self, address: &Address) -> Result<Option<Account>, StoreError>;
+
+    /// Every stored account, ordered by address
+    fn accounts(&self) -> Result<Vec<(Address, Account)>, StoreError>;
+
+    /// Apply a batch of state changes atomically
+    fn write_batch(&mut self, batch: StateBatch) -> Result<(), StoreError>;
+
+    /// Active-chain block at `height`
+    fn block_at_height(&self, height: u64) -> Result<Option<Block>, StoreError> {
+        match self.hash_at_height(height)? {
+            Some(hash) => self.block(&hash),
+            None => Ok(None),
+        }
+    }
+
+    /// Set the tip of the active chain
+    fn put_tip(&mut self, tip: Hash32) -> Result<(), StoreError> {
+        self.write_batch(StateBatch {
+            tip: Some(tip),
+            ..StateBatch::default()
+        })
+    }
+}
+
+/// State changes written to a [`ChainStore`] as one unit
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateBatch {
+    /// New tip of the active chain, if it changed
+    pub tip: Option<Hash32>,
+    /// Active-chain blocks (hash and undo data) to set, or to remove if `None`, by height
+    pub active: BTreeMap<u64, Option<(Hash32, StateUndo)>>,
+    /// Accounts to set, or to remove if `None`
+    pub accounts: BTreeMap<Address, Option<Account>>,
+}
+
+/// Chain state as built from state batches
+#[derive(Debug, Clone, Default)]
+struct StoredState {
+    tip: Option<Hash32>,
+    active: BTreeMap<u64, (Hash32, StateUndo)>,
+    accounts: BTreeMap<Address, Account>,
+}
+
+impl StoredState {
+    fn apply(&mut self, batch: StateBatch) {
+        if batch.tip.is_some() {
+            self.tip = batch.tip;
+        }
+        for (height, entry) in batch.active {
+            match entry {
+                Some(entry) => self.active.insert(height, entry),
+                None => self.active.remove(&height),
+            };
+        }
+        for (address, account) in batch.accounts {
+            match account {
+                Some(account) => self.accounts.insert(address, account),
+                None => self.accounts.remove(&address),
+            };
+        }
+    }
+
+    /// A single batch recreating this state from scratch
+    fn snapshot(&self) -> StateBatch {
+        StateBatch {
+            tip: self.tip,
+            active: self.active.iter().map(|(height, entry)| (*height, Some(entry.clone()))).collect(),
+            accounts: self.accounts.iter().map(|(address, account)| (*address, Some(*account))).collect(),
+        }
+    }
+
+    fn hash_at_height(&self, height: u64) -> Option<Hash32> {
+        self.active.get(&height).map(|(hash, _)| *hash)
+    }
+
+    fn undo_at_height(&self, height: u64) -> Option<StateUndo> {
+        self.active.get(&height).map(|(_, undo)| undo.clone())
+    }
+
+    fn accounts(&self) -> Vec<(Address, Account)> {
+        self.accounts.iter().map(|(address, account)| (*address, *account)).collect()
+    }
+}
+
+/// Chain store kept entirely in memory, for tests and throwaway chains
+#[derive(Debug, Clone)]
+pub struct MemoryStore {
+    genesis: GenesisConfig,
+    blocks: HashMap<Hash32, Block>,
+    /// Hashes of all stored blocks in the order they were stored
+    order: Vec<Hash32>,
+    state: StoredState,
+}
+
+impl MemoryStore {
+    /// Create an empty store for a chain with the given rules
+    pub fn new(genesis: GenesisConfig) -> Self {
+        MemoryStore {
+            genesis,
+            blocks: HashMap::new(),
+            order: Vec::new(),
+            state: StoredState::default(),
+        }
+    }
+}
+
+impl ChainStore for MemoryStore {
+    fn genesis(&self) -> &GenesisConfig {
+        &self.genesis
+    }
+
+    fn block(&self, hash: &Hash32) -> Result<Option<Block>, StoreError> {
+        Ok(self.blocks.get(hash).cloned())
+    }
+
+    fn put_block(&mut self, block: &Block) -> Result<(), StoreError> {
+        let hash = block.calculate_hash();
+        if let Entry::Vacant(entry) = self.blocks.entry(hash) {
+            entry.insert(block.clone());
+            self.order.push(hash);
+        }
+        Ok(())
+    }
+
+    fn blocks(&self) -> Result<Vec<Block>, StoreError> {
+        Ok(self.order.iter().map(|hash| self.blocks[hash].clone()).collect())
+    }
+
+    fn tip(&self) -> Result<Option<Hash32>, StoreError> {
+        Ok(self.state.tip)
+    }
+
+    fn hash_at_height(&self, height: u64) -> Result<Option<Hash32>, StoreError> {
+        Ok(self.state.hash_at_height(height))
+    }
+
+    fn undo_at_height(&self, height: u64) -> Result<Option<StateUndo>, StoreError> {
+        Ok(self.state.undo_at_height(height))
+    }
+
+    fn account(&self, address: &Address) -> Result<Option<Account>, StoreError> {
+        Ok(self.state.accounts.get(address).copied())
+    }
+
+    fn accounts(&self) -> Result<Vec<(Address, Account)>, StoreError> {
+        Ok(self.state.accounts())
+    }
+
+    fn write_batch(&mut self, batch: StateBatch) -> Result<(), StoreError> {
+        self.state.apply(batch);
+        Ok(())
+    }
+}
+
+/// Chain store kept in a directory: blocks in a [`BlockStore`], state in a log of batches
+#[derive(Debug)]
+pub struct FileStore {
+    blocks: BlockStore,
+    state: StoredState,
+    /// Length of the state log
+    state_len: u64,
+}
+
+impl FileStore {
+    /// Create an empty store in `dir`, creating the directory if needed
+    pub fn create(dir: impl AsRef<Path>, genesis: GenesisConfig) -> Result<Self, StoreError> {
+        Ok(FileStore {
+            blocks: BlockStore::create(dir, genesis)?,
+            state: StoredState::default(),
+            state_len: 0,
+        })
+    }
+
+    /// Open the store in `dir`
+    ///
+    /// A torn record at the end of the last segment or of the state log is
+    /// dropped; any other damaged record is an error and leaves the files as they
+    /// are. Once the whole state log has been replayed it is compacted into a
+    /// single snapshot.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let blocks = BlockStore::open(dir)?;
+        let log_path = blocks.path().join(STATE_LOG_FILE);
+        let mut state = StoredState::default();
+        let data = match fs::read(&log_path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut offset = 0;
+        while offset < data.len() {
+            let len = match read_record(&data[offset..], STATE_RECORD_MAGIC) {
+                RecordRead::Valid(len) => len,
+                // Only a torn final batch may be dropped
+                RecordRead::Torn => break,
+                RecordRead::Corrupt => return Err(StoreError::CorruptStateRecord { offset: offset as u64 }),
+            };
+            let start = offset + RECORD_HEADER_LEN;
+            state.apply(serde_json::from_slice(&data[start..start + len as usize])?);
+            offset = start + len as usize;
+        }
+
+        // Rewrite the log as one record so it does not grow across restarts
+        let snapshot = encode_record(STATE_RECORD_MAGIC, &serde_json::to_vec(&state.snapshot())?);
+        let tmp_path = blocks.path().join(format!("{}.tmp", STATE_LOG_FILE));
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&snapshot)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &log_path)?;
+        sync_dir(blocks.path())?;
+
+        Ok(FileStore {
+            blocks,
+            state,
+            state_len: snapshot.len() as u64,
+        })
+    }
+
+    /// Block segments of the store
+    pub fn block_store(&self) -> &BlockStore {
+        &self.blocks
+    }
+}
+
+impl ChainStore for FileStore {
+    fn genesis(&self) -> &GenesisConfig {
+        self.blocks.genesis()
+    }
+
+    fn block(&self, hash: &Hash32) -> Result<Option<Block>, StoreError> {
+        self.blocks.get(hash)
+    }
+
+    fn put_block(&mut self, block: &Block) -> Result<(), StoreError> {
+        self.blocks.append(block).map(|_| ())
+    }
+
+    fn blocks(&self) -> Result<Vec<Block>, StoreError> {
+        self.blocks.blocks()
+    }
+
+    fn tip(&self) -> Result<Option<Hash32>, StoreError> {
+        Ok(self.state.tip)
+    }
+
+    fn hash_at_height(&self, height: u64) -> Result<Option<Hash32>, StoreError> {
+        Ok(self.state.hash_at_height(height))
+    }
+
+    fn undo_at_height(&self, height: u64) -> Result<Option<StateUndo>, StoreError> {
+        Ok(self.state.undo_at_height(height))
+    }
+
+    fn account(&self, address: &Address) -> Result<Option<Account>, StoreError> {
+        Ok(self.state.accounts.get(address).copied())
+    }
+
+    fn accounts(&self) -> Result<Vec<(Address, Account)>, StoreError> {
+        Ok(self.state.accounts())
+    }
+
+    fn write_batch(&mut self, batch: StateBatch) -> Result<(), StoreError> {
+        let record = encode_record(STATE_RECORD_MAGIC, &serde_json::to_vec(&batch)?);
+        append_record(&self.blocks.path().join(STATE_LOG_FILE), self.state_len, &record)?;
+        if self.state_len == 0 {
+            sync_dir(self.blocks.path())?;
+        }
+        self.state_len += record.len() as u64;
+        self.state.apply(batch);
+        Ok(())
+    }
+}
+
+/// Position of a record in the store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RecordLocation {
+    segment: u32,
+    /// Offset of the record header in the segment
+    offset: u64,
+    /// Length of the block encoding following the header
+    len: u32,
+}
+
+/// Append-only block storage in segment files, the block half of a [`FileStore`]
+#[derive(Debug)]
+pub struct BlockStore {
+    dir: PathBuf,
+    genesis: GenesisConfig,
+    max_segment_size: u64,
+    /// Segment new records are appended to
+    current_segment: u32,
+    /// Length of the current segment
+    current_len: u64,
+    /// Hashes of all stored blocks in the order they were appended
+    order: Vec<Hash32>,
+    locations: HashMap<Hash32, RecordLocation>,
+    by_height: BTreeMap<u64, Vec<Hash32>>,
+}
+
+impl BlockStore {
+    /// Create an empty store in `dir`, creating the directory if needed
+    pub fn create(dir: impl AsRef<Path>, genesis: GenesisConfig) -> Result<Self, StoreError> {
+        let dir = dir.as_ref().to_path_buf();
+        let genesis_path = dir.join(GENESIS_FILE);
+        if genesis_path.exists() {
+            return Err(StoreError::AlreadyExists(dir));
+        }
+        fs::create_dir_all(&dir)?;
+
+        // Write the configuration atomically so a crash never leaves half of it behind
+        let tmp_path = dir.join(format!("{}.tmp", GENESIS_FILE));
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(serde_json::to_string_pretty(&genesis)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &genesis_path)?;
+        sync_dir(&dir)?;
+
+        Ok(BlockStore {
+            dir,
+            genesis,
+            max_segment_size: DEFAULT_MAX_SEGMENT_SIZE,
+            current_segment: 0,
+            current_len: 0,
+            order: Vec::new(),
+            locations: HashMap::new(),
+            by_height: BTreeMap::new(),
+        })
+    }
+
+    /// Whether a store has been created in `dir`
+    pub fn exists(dir: impl AsRef<Path>) -> bool {
+        dir.as_ref().join(GENESIS_FILE).exists()
+    }
+
+    /// Open the store in `dir` and rebuild its index
+    ///
+    /// A torn record at the end of the last segment is truncated away;
+    /// any other damaged record is reported as [`StoreError::CorruptRecord`].
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let dir = dir.as_ref().to_path_buf();
+        let genesis = serde_json::from_str(&fs::read_to_string(dir.join(GENESIS_FILE))?)?;
+        let mut store = BlockStore {
+            dir,
+            genesis,
+            max_segment_size: DEFAULT_MAX_SEGMENT_SIZE,
+            current_segment: 0,
+            current_len: 0,
+            order: Vec::new(),
+            locations: HashMap::new(),
+            by_height: BTreeMap::new(),
+        };
+
+        let segments = store.segment_numbers()?;
+        for (position, &segment) in segments.iter().enumerate() {
+            if segment != position as u32 {
+                return Err(StoreError::MissingSegment(position as u32));
+            }
+            store.index_segment(segment, position + 1 == segments.len())?;
+        }
+        Ok(store)
+    }
+
+    /// Use `bytes` as the size limit for segment files
+    ///
+    /// A record larger than the limit still gets a segment of its own.
+    pub fn with_max_segment_size(mut self, bytes: u64) -> Self {
+        self.max_segment_size = bytes;
+        self
+    }
+
+    /// Directory of the store
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Genesis configuration of the stored chain
+    pub fn genesis(&self) -> &GenesisConfig {
+        &self.genesis
+    }
+
+    /// Number of stored blocks
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Whether the store holds no blocks
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Number of segment files in use
+    pub fn segment_count(&self) -> u32 {
+        if self.current_len == 0 {
+            self.current_segment
+        } else {
+            self.current_segment + 1
+        }
+    }
+
+    /// Whether the block `hash` is stored
+    pub fn contains(&self, hash: &Hash32) -> bool {
+        self.locations.contains_key(hash)
+    }
+
+    /// Hashes of the stored blocks at `height`, on any branch, in the order they were stored
+    pub fn hashes_at_height(&self, height: u64) -> &[Hash32] {
+        self.by_height.get(&height).map_or(&[], Vec::as_slice)
+    }
+
+    /// Read the block `hash` from disk
+    pub fn get(&self, hash: &Hash32) -> Result<Option<Block>, StoreError> {
+        let location = match self.locations.get(hash) {
+            Some(location) => *location,
+            None => return Ok(None),
+        };
+        let corrupt = StoreError::CorruptRecord { segment: location.segment, offset: location.offset };
+        let mut file = File::open(self.segment_path(location.segment))?;
+        file.seek(SeekFrom::Start(location.offset))?;
+        let mut record = vec![0u8; RECORD_HEADER_LEN + location.len as usize];
+        match file.read_exact(&mut record) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(corrupt),
+            Err(e) => return Err(e.into()),
+        }
+        match read_record(&record, RECORD_MAGIC) {
+            RecordRead::Valid(len) if len == location.len => {
+                Block::from_bytes(&record[RECORD_HEADER_LEN..]).map(Some).map_err(|_| corrupt)
+            }
+            _ => Err(corrupt),
+        }
+    }
+
+    /// Read every stored block in the order it was appended
+    pub fn blocks(&self) -> Result<Vec<Block>, StoreError> {
+        let mut blocks = Vec::with_capacity(self.order.len());
+        let mut segment: Option<(u32, Vec<u8>)> = None;
+        for hash in &self.order {
+            let location = self.locations[hash];
+            if segment.as_ref().map(|(number, _)| *number) != Some(location.segment) {
+                segment = Some((location.segment, fs::read(self.segment_path(location.segment))?));
+            }
+            let (_, data) = segment.as_ref().expect("segment was just read");
+            blocks.push(decode_record(data, location)?);
+        }
+        Ok(blocks)
+    }
+
+    /// Append a block and flush it to disk
+    ///
+    /// Returns `false` without writing anything if the block is already stored.
+    pub fn append(&mut self, block: &Block) -> Result<bool, StoreError> {
+        let hash = block.calculate_hash();
+        if self.contains(&hash) {
+            return Ok(false);
+        }
+
+        let payload = block.to_bytes();
+        let record = encode_record(RECORD_MAGIC, &payload);
+        if self.current_len > 0 && self.current_len + record.len() as u64 > self.max_segment_size {
+            self.current_segment += 1;
+            self.current_len = 0;
+        }
+
+        append_record(&self.segment_path(self.current_segment), self.current_len, &record)?;
+        if self.current_len == 0 {
+            sync_dir(&self.dir)?;
+        }
+
+        let location = RecordLocation {
+            segment: self.current_segment,
+            offset: self.current_len,
+            len: payload.len() as u32,
+        };
+        self.current_len += record.len() as u64;
+        self.index(hash, block.header.block_height, location);
+        Ok(true)
+    }
+
+    fn index(&mut self, hash: Hash32, height: u64, location: RecordLocation) {
+        self.order.push(hash);
+        self.locations.insert(hash, location);
+        self.by_height.entry(height).or_default().push(hash);
+    }
+
+    fn segment_path(&self, segment: u32) -> PathBuf {
+        self.dir.join(format!("blk{:05}.dat", segment))
+    }
+
+    /// Numbers of the segment files in the store directory, in ascending order
+    fn segment_numbers(&self) -> Result<Vec<u32>, StoreError> {
+        let mut numbers = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            let number = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("blk"))
+                .and_then(|name| name.strip_suffix(".dat"))
+                .and_then(|number| number.parse().ok());
+            if let Some(number) = number {
+                numbers.push(number);
+            }
+        }
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
+
+    /// Index every record of a segment
+    ///
+    /// If `last` is set, a torn record at the end is cut off: the segment is
+    /// truncated before it and indexing stops there.
+    fn index_segment(&mut self, segment: u32, last: bool) -> Result<(), StoreError> {
+        let path = self.segment_path(segment);
+        let data = fs::read(&path)?;
+        let mut offset = 0usize;
+        while offset < data.len() {
+            let len = match read_record(&data[offset..], RECORD_MAGIC) {
+                RecordRead::Valid(len) => len,
+                // Only a torn final append may be cut off
+                RecordRead::Torn if last => {
+                    let file = OpenOptions::new().write(true).open(&path)?;
+                    file.set_len(offset as u64)?;
+                    file.sync_data()?;
+                    break;
+                }
+                RecordRead::Torn | RecordRead::Corrupt => {
+                    return Err(StoreError::CorruptRecord { segment, offset: offset as u64 });
+                }
+            };
+            let location = RecordLocation { segment, offset: offset as u64, len };
+            let block = decode_record(&data, location)?;
+            self.index(block.calculate_hash(), block.header.block_height, location);
+            offset += RECORD_HEADER_LEN + len as usize;
+        }
+        self.current_segment = segment;
+        self.current_len = offset as u64;
+        Ok(())
+    }
+}
+
+/// Frame `payload` as a record
+fn encode_record(magic: [u8; 4], payload: &[u8]) -> Vec<u8> {
+    Encoder::new()
+        .fixed(&magic)
+        .length(payload.len())
+        .fixed(Hash32::digest(payload).as_bytes())
+        .fixed(payload)
+        .finish()
+}
+
+/// Append a record to the file at `path`, currently `len` bytes long, and flush it to disk
+fn append_record(path: &Path, len: u64, record: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if let Err(e) = file.write_all(record).and_then(|()| file.sync_data()) {
+        // Cut off whatever part of the record made it to the file
+        let _ = file.set_len(len);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// What was found reading a record from the start of some data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordRead {
+    /// A record with the expected magic number and a matching checksum, with
+    /// this payload length
+    Valid(u32),
+    /// What a torn final append leaves behind: a record that runs past the end
+    /// of the data, one that ends exactly there but fails its checksum, or zeros
+    /// up to the end where a file was extended but the record never written
+    Torn,
+    /// A record with the wrong magic number, or that fails its checksum and is
+    /// followed by more data
+    Corrupt,
+}
+
+/// Read the record at the start of `data`
+fn read_record(data: &[u8], expected_magic: [u8; 4]) -> RecordRead {
+    if data.iter().all(|&byte| byte == 0) {
+        return RecordRead::Torn;
+    }
+    let magic_len = data.len().min(expected_magic.len());
+    if data[..magic_len] != expected_magic[..magic_len] {
+        return RecordRead::Corrupt;
+    }
+    let Some(header) = data.get(..RECORD_HEADER_LEN) else {
+        return RecordRead::Torn;
+    };
+    let mut header = Decoder::new(&header[expected_magic.len()..]);
+    let (Ok(len), Ok(checksum)) = (header.u32(), header.fixed()) else {
+        return RecordRead::Corrupt;
+    };
+    let end = RECORD_HEADER_LEN + len as usize;
+    match data.get(RECORD_HEADER_LEN..end) {
+        Some(payload) if Hash32::digest(payload) == Hash32::from_bytes(checksum) => RecordRead::Valid(len),
+        Some(_) if end == data.len() => RecordRead::Torn,
+        Some(_) => RecordRead::Corrupt,
+        None => RecordRead::Torn,
+    }
+}
+
+/// Decode the block of the record at `location` in the segment contents `data`
+fn decode_record(data: &[u8], location: RecordLocation) -> Result<Block, StoreError> {
+    let corrupt = StoreError::CorruptRecord { segment: location.segment, offset: location.offset };
+    let start = location.offset as usize + RECORD_HEADER_LEN;
+    let payload = match data.get(start..start + location.len as usize) {
+        Some(payload) => payload,
+        None => return Err(corrupt),
+    };
+    Block::from_bytes(payload).map_err(|_| corrupt)
+}
+
+/// Flush a directory entry change (a created or renamed file) to disk
+#[cfg(unix)]
+pub(crate) fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
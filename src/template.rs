@@ -0,0 +1,149 @@
+//! Block templates: the block a miner works on next
+//!
+//! [`BlockTemplateBuilder`] takes candidate transactions in the order they should
+//! be mined (see [`Mempool::ordered`](crate::Mempool::ordered)) and keeps each one
+//! that follows its sender's nonce, is affordable on top of the sender's
+//! transactions selected before it and still fits within the block size and
+//! transaction count limits. Once a sender's transaction is left out, the
+//! sender's later nonces are left out too. Transactions that are not selected
+//! stay in the pending pool for a later block.
+
+use crate::address::Address;
+use crate::consensus::ConsensusParams;
+use crate::error::BlockError;
+use crate::primitives::Hash32;
+use crate::state::AccountState;
+use crate::{Block, Transaction};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+
+/// An unmined block together with what it collects
+#[derive(Debug, Clone)]
+pub struct BlockTemplate {
+    /// The block: a coinbase followed by the selected transactions, with the nonce
+    /// still to be found
+    pub block: Block,
+    /// Fees of the selected transactions, included in the coinbase
+    pub fees: u64,
+}
+
+impl BlockTemplate {
+    /// The selected transactions, without the coinbase
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.block.transactions[1..]
+    }
+}
+
+/// Builds the next block on top of a chain tip within the consensus limits
+///
+/// Created with [`Blockchain::block_template_builder`](crate::Blockchain::block_template_builder).
+#[derive(Debug, Clone)]
+pub struct BlockTemplateBuilder<'a> {
+    params: &'a ConsensusParams,
+    state: &'a AccountState,
+    chain_id: Hash32,
+    height: u64,
+    parent_hash: Hash32,
+    bits: u32,
+    timestamp: DateTime<Utc>,
+    max_size: u64,
+    max_transactions: u32,
+}
+
+impl<'a> BlockTemplateBuilder<'a> {
+    /// Builder for the block at `height` on top of `parent_hash`, validating
+    /// transactions against `state`, the account state at the parent
+    pub fn new(
+        params: &'a ConsensusParams,
+        state: &'a AccountState,
+        chain_id: Hash32,
+        height: u64,
+        parent_hash: Hash32,
+        bits: u32,
+        timestamp: DateTime<Utc>,
+    ) -> Self {
+        BlockTemplateBuilder {
+            params,
+            state,
+            chain_id,
+            height,
+            parent_hash,
+            bits,
+            timestamp,
+            max_size: params.max_block_size,
+            max_transactions: params.max_block_transactions,
+        }
+    }
+
+    /// Build a smaller block than the consensus limit allows, in encoded bytes
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size.min(self.params.max_block_size);
+        self
+    }
+
+    /// Build a block with fewer transactions, including the coinbase, than the
+    /// consensus limit allows; the coinbase is always included
+    pub fn with_max_transactions(mut self, max_transactions: u32) -> Self {
+        self.max_transactions = max_transactions.min(self.params.max_block_transactions).max(1);
+        self
+    }
+
+    /// Select from `candidates`, in order, the transactions that fit and build the
+    /// block paying the subsidy and their fees to `miner`
+    pub fn build<'t>(
+        self,
+        miner: &Address,
+        candidates: impl IntoIterator<Item = &'t Transaction>,
+    ) -> Result<BlockTemplate, BlockError> {
+        let subsidy = self.params.monetary.block_subsidy(self.height);
+        let coinbase = Transaction::coinbase(self.chain_id, miner, subsidy, self.height);
+        // Fixed-width fields only: the size does not change once fees are added
+        let mut size = Block::new(self.height, self.parent_hash, vec![coinbase], self.bits).size() as u64;
+
+        let mut selected: Vec<Transaction> = Vec::new();
+        let mut fees = 0u64;
+        let mut accounts: HashMap<Address, (u64, u64)> = HashMap::new();
+        let mut excluded: HashSet<Address> = HashSet::new();
+        for tx in candidates {
+            if selected.len() + 1 >= self.max_transactions as usize {
+                break;
+            }
+            let Ok(sender) = tx.sender_address() else {
+                continue;
+            };
+            if tx.is_coinbase() || excluded.contains(&sender) {
+                continue;
+            }
+            let (next_nonce, balance) = accounts.entry(sender).or_insert_with(|| {
+                let account = self.state.account(&sender);
+                (account.nonce, account.balance)
+            });
+            if tx.nonce != *next_nonce {
+                continue;
+            }
+            let tx_size = tx.size() as u64;
+            let required = tx.amount.checked_add(tx.fee).filter(|required| required <= balance);
+            let total_fees = fees.checked_add(tx.fee);
+            match (required, total_fees) {
+                (Some(required), Some(total_fees)) if size + tx_size <= self.max_size => {
+                    *next_nonce += 1;
+                    *balance -= required;
+                    size += tx_size;
+                    fees = total_fees;
+                    selected.push(tx.clone());
+                }
+                _ => {
+                    excluded.insert(sender);
+                }
+            }
+        }
+
+        let reward = subsidy.checked_add(fees).ok_or(BlockError::FeeOverflow)?;
+        let mut transactions = Vec::with_capacity(selected.len() + 1);
+        transactions.push(Transaction::coinbase(self.chain_id, miner, reward, self.height));
+        transactions.extend(selected);
+        let mut block = Block::new(self.height, self.parent_hash, transactions, self.bits);
+        block.header.timestamp = self.timestamp;
+        Ok(BlockTemplate { block, fees })
+    }
+}
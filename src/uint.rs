@@ -0,0 +1,228 @@
+//! Minimal unsigned 256-bit integer for Proof of Work targets and chain work
+//!
+//! Only the operations needed by the difficulty code are implemented; values are
+//! stored as four little-endian `u64` limbs.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Unsigned 256-bit integer
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct U256([u64; 4]);
+
+impl U256 {
+    /// Zero
+    pub const ZERO: U256 = U256([0; 4]);
+    /// One
+    pub const ONE: U256 = U256([1, 0, 0, 0]);
+    /// Largest representable value, 2^256 - 1
+    pub const MAX: U256 = U256([u64::MAX; 4]);
+
+    /// Convert from a `u64`
+    pub const fn from_u64(value: u64) -> Self {
+        U256([value, 0, 0, 0])
+    }
+
+    /// Interpret 32 bytes as a big-endian integer
+    pub fn from_be_bytes(bytes: &[u8; 32]) -> Self {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let start = 32 - (i + 1) * 8;
+            *limb = u64::from_be_bytes(bytes[start..start + 8].try_into().expect("8-byte slice"));
+        }
+        U256(limbs)
+    }
+
+    /// Big-endian byte representation
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.0.iter().enumerate() {
+            let start = 32 - (i + 1) * 8;
+            bytes[start..start + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Whether the value is zero
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    /// Lowest 64 bits
+    pub fn low_u64(&self) -> u64 {
+        self.0[0]
+    }
+
+    /// Number of significant bits (0 for zero)
+    pub fn bits(&self) -> u32 {
+        for i in (0..4).rev() {
+            if self.0[i] != 0 {
+                return 64 * i as u32 + (64 - self.0[i].leading_zeros());
+            }
+        }
+        0
+    }
+
+    /// Number of leading zero bits in the 256-bit representation
+    pub fn leading_zeros(&self) -> u32 {
+        256 - self.bits()
+    }
+
+    /// Shift left by `shift` bits, dropping bits shifted out
+    pub fn shl(&self, shift: u32) -> Self {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        let limb_shift = (shift / 64) as usize;
+        let bit_shift = shift % 64;
+        let mut result = [0u64; 4];
+        for i in (limb_shift..4).rev() {
+            let src = i - limb_shift;
+            result[i] = self.0[src] << bit_shift;
+            if bit_shift > 0 && src > 0 {
+                result[i] |= self.0[src - 1] >> (64 - bit_shift);
+            }
+        }
+        U256(result)
+    }
+
+    /// Shift right by `shift` bits
+    pub fn shr(&self, shift: u32) -> Self {
+        if shift >= 256 {
+            return U256::ZERO;
+        }
+        let limb_shift = (shift / 64) as usize;
+        let bit_shift = shift % 64;
+        let mut result = [0u64; 4];
+        for (i, limb) in result.iter_mut().take(4 - limb_shift).enumerate() {
+            let src = i + limb_shift;
+            *limb = self.0[src] >> bit_shift;
+            if bit_shift > 0 && src + 1 < 4 {
+                *limb |= self.0[src + 1] << (64 - bit_shift);
+            }
+        }
+        U256(result)
+    }
+
+    /// Addition returning `None` on overflow
+    pub fn checked_add(&self, other: &U256) -> Option<Self> {
+        let mut result = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in result.iter_mut().enumerate() {
+            let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = c1 || c2;
+        }
+        (!carry).then_some(U256(result))
+    }
+
+    /// Addition clamped to [`U256::MAX`]
+    pub fn saturating_add(&self, other: &U256) -> Self {
+        self.checked_add(other).unwrap_or(U256::MAX)
+    }
+
+    /// Subtraction returning `None` on underflow
+    pub fn checked_sub(&self, other: &U256) -> Option<Self> {
+        let mut result = [0u64; 4];
+        let mut borrow = false;
+        for (i, limb) in result.iter_mut().enumerate() {
+            let (diff, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            *limb = diff;
+            borrow = b1 || b2;
+        }
+        (!borrow).then_some(U256(result))
+    }
+
+    /// Multiplication by a `u64` returning `None` on overflow
+    pub fn checked_mul_u64(&self, factor: u64) -> Option<Self> {
+        let mut result = [0u64; 4];
+        let mut carry: u128 = 0;
+        for (i, limb) in result.iter_mut().enumerate() {
+            let product = self.0[i] as u128 * factor as u128 + carry;
+            *limb = product as u64;
+            carry = product >> 64;
+        }
+        (carry == 0).then_some(U256(result))
+    }
+
+    /// Division by a non-zero `u64`
+    pub fn div_u64(&self, divisor: u64) -> Self {
+        assert!(divisor != 0, "division by zero");
+        let mut result = [0u64; 4];
+        let mut remainder: u128 = 0;
+        for i in (0..4).rev() {
+            let current = (remainder << 64) | self.0[i] as u128;
+            result[i] = (current / divisor as u128) as u64;
+            remainder = current % divisor as u128;
+        }
+        U256(result)
+    }
+
+    /// Division by a non-zero `U256` (schoolbook shift-and-subtract)
+    pub fn div(&self, divisor: &U256) -> Self {
+        assert!(!divisor.is_zero(), "division by zero");
+        if self < divisor {
+            return U256::ZERO;
+        }
+
+        let shift = divisor.leading_zeros() - self.leading_zeros();
+        let mut remainder = *self;
+        let mut shifted = divisor.shl(shift);
+        let mut quotient = U256::ZERO;
+        for i in (0..=shift).rev() {
+            if let Some(rest) = remainder.checked_sub(&shifted) {
+                remainder = rest;
+                quotient.0[(i / 64) as usize] |= 1 << (i % 64);
+            }
+            shifted = shifted.shr(1);
+        }
+        quotient
+    }
+
+    /// Bitwise complement
+    pub fn not(&self) -> Self {
+        U256([!self.0[0], !self.0[1], !self.0[2], !self.0[3]])
+    }
+
+    /// Approximate value as a float
+    pub fn to_f64(&self) -> f64 {
+        self.0
+            .iter()
+            .rev()
+            .fold(0.0, |acc, limb| acc * 18_446_744_073_709_551_616.0 + *limb as f64)
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<u64> for U256 {
+    fn from(value: u64) -> Self {
+        U256::from_u64(value)
+    }
+}
+
+impl fmt::Debug for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "U256({:x})", self)
+    }
+}
+
+impl fmt::LowerHex for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = hex::encode(self.to_be_bytes());
+        let trimmed = hex.trim_start_matches('0');
+        f.pad_integral(true, "0x", if trimmed.is_empty() { "0" } else { trimmed })
+    }
+}
@@ -0,0 +1,384 @@
+//! Wallet and encrypted keystore for I Protocol
+//!
+//! Keys are Ed25519 signing keys. The keystore is a versioned JSON file: public
+//! keys and labels are stored in the clear so they can be listed without a
+//! password, while every secret key is sealed with ChaCha20-Poly1305 under a
+//! key derived from the wallet password with Argon2id.
+
+use crate::primitives::{Hash32, PublicKey};
+use crate::storage::sync_dir;
+use crate::Transaction;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signer, SigningKey};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Current keystore file format version
+pub const KEYSTORE_VERSION: u32 = 1;
+
+/// Length of the Argon2 salt stored in the keystore
+const SALT_LEN: usize = 16;
+/// Length of the ChaCha20-Poly1305 nonce used per sealed key
+const NONCE_LEN: usize = 12;
+/// Context string for the password verifier stored in the keystore
+const VERIFIER_CONTEXT: &[u8] = b"i-protocol keystore password verifier v1";
+
+/// Errors produced by wallet and keystore operations
+#[derive(Debug, Error)]
+pub enum WalletError {
+    #[error("keystore I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("keystore format error: {0}")]
+    Format(#[from] serde_json::Error),
+    #[error("keystore already exists at {0}")]
+    AlreadyExists(PathBuf),
+    #[error("unsupported keystore version {0} (expected {KEYSTORE_VERSION})")]
+    UnsupportedVersion(u32),
+    #[error("invalid keystore password")]
+    InvalidPassword,
+    #[error("key derivation failed: {0}")]
+    KeyDerivation(String),
+    #[error("keystore entry for {0} is corrupted")]
+    CorruptedEntry(PublicKey),
+    #[error("key {0} is already in the wallet")]
+    DuplicateKey(PublicKey),
+    #[error("no key for public key {0} in the wallet")]
+    KeyNotFound(PublicKey),
+    #[error("invalid key material: {0}")]
+    InvalidKey(String),
+}
+
+/// Argon2id cost parameters used to derive the keystore encryption key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of passes over memory
+    pub iterations: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Public information about a key held in the wallet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyInfo {
+    /// Ed25519 public key
+    pub public_key: PublicKey,
+    /// User supplied label
+    pub label: String,
+    /// When the key was generated or imported
+    pub created_at: DateTime<Utc>,
+}
+
+/// On-disk keystore file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    kdf: KdfSection,
+    verifier: Hash32,
+    keys: Vec<KeystoreEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KdfSection {
+    algorithm: String,
+    salt: String,
+    params: KdfParams,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeystoreEntry {
+    public_key: PublicKey,
+    label: String,
+    created_at: DateTime<Utc>,
+    cipher: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Wallet holding decrypted Ed25519 keys backed by an encrypted keystore file
+pub struct Wallet {
+    path: PathBuf,
+    file: KeystoreFile,
+    encryption_key: [u8; 32],
+    keys: Vec<SigningKey>,
+}
+
+/// Generate a fresh Ed25519 signing key from the operating system RNG
+pub fn generate_signing_key() -> SigningKey {
+    SigningKey::generate(&mut OsRng)
+}
+
+impl Wallet {
+    /// Create a new, empty keystore at `path` protected by `password`
+    pub fn create(path: impl AsRef<Path>, password: &str) -> Result<Self, WalletError> {
+        Self::create_with_params(path, password, KdfParams::default())
+    }
+
+    /// Create a new, empty keystore with explicit key derivation parameters
+    pub fn create_with_params(
+        path: impl AsRef<Path>,
+        password: &str,
+        params: KdfParams,
+    ) -> Result<Self, WalletError> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            return Err(WalletError::AlreadyExists(path));
+        }
+
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let encryption_key = derive_key(password, &salt, &params)?;
+
+        let file = KeystoreFile {
+            version: KEYSTORE_VERSION,
+            kdf: KdfSection {
+                algorithm: "argon2id".to_string(),
+                salt: hex::encode(salt),
+                params,
+            },
+            verifier: password_verifier(&encryption_key),
+            keys: Vec::new(),
+        };
+
+        let wallet = Wallet {
+            path,
+            file,
+            encryption_key,
+            keys: Vec::new(),
+        };
+        wallet.save()?;
+        Ok(wallet)
+    }
+
+    /// Open an existing keystore and decrypt its keys with `password`
+    pub fn open(path: impl AsRef<Path>, password: &str) -> Result<Self, WalletError> {
+        let path = path.as_ref().to_path_buf();
+        let file: KeystoreFile = serde_json::from_str(&fs::read_to_string(&path)?)?;
+
+        if file.version != KEYSTORE_VERSION {
+            return Err(WalletError::UnsupportedVersion(file.version));
+        }
+        if file.kdf.algorithm != "argon2id" {
+            return Err(WalletError::KeyDerivation(format!(
+                "unsupported algorithm {}",
+                file.kdf.algorithm
+            )));
+        }
+
+        let salt = hex::decode(&file.kdf.salt)
+            .map_err(|e| WalletError::KeyDerivation(format!("invalid salt: {}", e)))?;
+        let encryption_key = derive_key(password, &salt, &file.kdf.params)?;
+        // Hash32 equality runs in constant time
+        if password_verifier(&encryption_key) != file.verifier {
+            return Err(WalletError::InvalidPassword);
+        }
+
+        let keys = file
+            .keys
+            .iter()
+            .map(|entry| open_entry(&encryption_key, entry))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Wallet {
+            path,
+            file,
+            encryption_key,
+            keys,
+        })
+    }
+
+    /// Path of the backing keystore file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Generate a new key, persist it and return its public key
+    pub fn generate_key(&mut self, label: &str) -> Result<PublicKey, WalletError> {
+        self.insert_key(generate_signing_key(), label)
+    }
+
+    /// Import an existing 32-byte Ed25519 secret key
+    pub fn import_key(&mut self, secret_key: &[u8], label: &str) -> Result<PublicKey, WalletError> {
+        let bytes: [u8; 32] = secret_key
+            .try_into()
+            .map_err(|_| WalletError::InvalidKey(format!("expected 32 bytes, got {}", secret_key.len())))?;
+        self.insert_key(SigningKey::from_bytes(&bytes), label)
+    }
+
+    /// Export the 32-byte secret key for the given public key
+    pub fn export_key(&self, public_key: &PublicKey) -> Result<[u8; 32], WalletError> {
+        Ok(self.signing_key(public_key)?.to_bytes())
+    }
+
+    /// List the public information of all keys in the wallet
+    pub fn list_keys(&self) -> Vec<KeyInfo> {
+        self.file
+            .keys
+            .iter()
+            .map(|entry| KeyInfo {
+                public_key: entry.public_key,
+                label: entry.label.clone(),
+                created_at: entry.created_at,
+            })
+            .collect()
+    }
+
+    /// Look up the signing key for a public key
+    pub fn signing_key(&self, public_key: &PublicKey) -> Result<&SigningKey, WalletError> {
+        self.keys
+            .iter()
+            .find(|key| PublicKey::from(&key.verifying_key()) == *public_key)
+            .ok_or(WalletError::KeyNotFound(*public_key))
+    }
+
+    /// Sign a transaction with the key matching its `from` public key
+    pub fn sign_transaction(&self, transaction: &mut Transaction) -> Result<(), WalletError> {
+        let signing_key = self.signing_key(&transaction.from)?;
+        let signature = signing_key.sign(&transaction.signing_message());
+        transaction.signature = Some(signature.into());
+        Ok(())
+    }
+
+    /// Seal a key, add it to the keystore and persist the file
+    fn insert_key(&mut self, signing_key: SigningKey, label: &str) -> Result<PublicKey, WalletError> {
+        let public_key = PublicKey::from(&signing_key.verifying_key());
+        if self.file.keys.iter().any(|entry| entry.public_key == public_key) {
+            return Err(WalletError::DuplicateKey(public_key));
+        }
+
+        let entry = seal_entry(&self.encryption_key, &signing_key, label)?;
+        self.file.keys.push(entry);
+        self.keys.push(signing_key);
+
+        if let Err(e) = self.save() {
+            self.file.keys.pop();
+            self.keys.pop();
+            return Err(e);
+        }
+        Ok(public_key)
+    }
+
+    /// Atomically and durably write the keystore file: write and sync a temporary
+    /// file only the owner can read, rename it over the keystore, then sync the
+    /// directory so the rename survives a crash
+    fn save(&self) -> Result<(), WalletError> {
+        let json = serde_json::to_string_pretty(&self.file)?;
+        let tmp_path = self.path.with_extension("tmp");
+        let mut tmp = create_private(&tmp_path)?;
+        tmp.write_all(json.as_bytes())?;
+        tmp.sync_all()?;
+        drop(tmp);
+        fs::rename(&tmp_path, &self.path)?;
+        let dir = self.path.parent().filter(|dir| !dir.as_os_str().is_empty());
+        sync_dir(dir.unwrap_or(Path::new(".")))?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Wallet")
+            .field("path", &self.path)
+            .field("keys", &self.list_keys())
+            .finish()
+    }
+}
+
+/// Derive the keystore encryption key from a password with Argon2id
+fn derive_key(password: &str, salt: &[u8], params: &KdfParams) -> Result<[u8; 32], WalletError> {
+    let argon_params = Params::new(params.memory_kib, params.iterations, params.parallelism, Some(32))
+        .map_err(|e| WalletError::KeyDerivation(e.to_string()))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| WalletError::KeyDerivation(e.to_string()))?;
+    Ok(key)
+}
+
+/// Password verifier stored in the keystore so a wrong password is detected on open
+fn password_verifier(encryption_key: &[u8; 32]) -> Hash32 {
+    Hash32::from_bytes(*blake3::keyed_hash(encryption_key, VERIFIER_CONTEXT).as_bytes())
+}
+
+/// Create a new file readable and writable only by its owner, replacing any
+/// leftover file at `path`
+fn create_private(path: &Path) -> io::Result<File> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// Encrypt a signing key, binding the ciphertext to its public key
+fn seal_entry(encryption_key: &[u8; 32], signing_key: &SigningKey, label: &str) -> Result<KeystoreEntry, WalletError> {
+    let public_key = signing_key.verifying_key();
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(encryption_key));
+    let secret = signing_key.to_bytes();
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: &secret, aad: public_key.as_bytes() })
+        .map_err(|_| WalletError::InvalidKey("encryption failed".to_string()))?;
+
+    Ok(KeystoreEntry {
+        public_key: PublicKey::from(&public_key),
+        label: label.to_string(),
+        created_at: Utc::now(),
+        cipher: "chacha20poly1305".to_string(),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+/// Decrypt a keystore entry and check it matches its stored public key
+fn open_entry(encryption_key: &[u8; 32], entry: &KeystoreEntry) -> Result<SigningKey, WalletError> {
+    let corrupted = || WalletError::CorruptedEntry(entry.public_key);
+
+    if entry.cipher != "chacha20poly1305" {
+        return Err(corrupted());
+    }
+    let nonce = hex::decode(&entry.nonce).map_err(|_| corrupted())?;
+    let ciphertext = hex::decode(&entry.ciphertext).map_err(|_| corrupted())?;
+    if nonce.len() != NONCE_LEN {
+        return Err(corrupted());
+    }
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(encryption_key));
+    let secret = cipher
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: entry.public_key.as_bytes() })
+        .map_err(|_| corrupted())?;
+    let secret: [u8; 32] = secret.as_slice().try_into().map_err(|_| corrupted())?;
+
+    let signing_key = SigningKey::from_bytes(&secret);
+    if PublicKey::from(&signing_key.verifying_key()) != entry.public_key {
+        return Err(corrupted());
+    }
+    Ok(signing_key)
+}